@@ -0,0 +1,163 @@
+use crate::ToDo;
+use crate::validation::FieldError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Error body returned by simple_server, serialized as
+/// `{"code": "not_found", "message": "..."}`
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ApiError {
+    BadRequest {
+        message: String,
+    },
+    NotFound {
+        message: String,
+    },
+    Conflict {
+        message: String,
+    },
+    /// The todo changed since the version the request was made against,
+    /// `current` is the stored copy
+    VersionConflict {
+        message: String,
+        current: Box<ToDo>,
+    },
+    Unprocessable {
+        message: String,
+    },
+    /// The payload breaks the validation rules of `common::validation`
+    Invalid {
+        message: String,
+        fields: Vec<FieldError>,
+    },
+    TooLarge {
+        message: String,
+    },
+    Internal {
+        message: String,
+    },
+    Unavailable {
+        message: String,
+    },
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::BadRequest {
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound {
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn version_conflict(current: ToDo) -> Self {
+        Self::VersionConflict {
+            message: format!(
+                "ToDo {} was changed in the meantime, it is at version {}",
+                current.id, current.version
+            ),
+            current: Box::new(current),
+        }
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::Unprocessable {
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid(fields: Vec<FieldError>) -> Self {
+        Self::Invalid {
+            message: "Validation failed".to_string(),
+            fields,
+        }
+    }
+
+    pub fn too_large(message: impl Into<String>) -> Self {
+        Self::TooLarge {
+            message: message.into(),
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::Internal {
+            message: message.into(),
+        }
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::Unavailable {
+            message: message.into(),
+        }
+    }
+
+    /// HTTP status the error is sent with
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadRequest { .. } => 400,
+            Self::NotFound { .. } => 404,
+            Self::Conflict { .. } => 409,
+            Self::VersionConflict { .. } => 409,
+            Self::Unprocessable { .. } => 422,
+            Self::Invalid { .. } => 422,
+            Self::TooLarge { .. } => 413,
+            Self::Internal { .. } => 500,
+            Self::Unavailable { .. } => 503,
+        }
+    }
+
+    /// Stable machine readable code, same as the `code` field of the body
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest { .. } => "bad_request",
+            Self::NotFound { .. } => "not_found",
+            Self::Conflict { .. } => "conflict",
+            Self::VersionConflict { .. } => "version_conflict",
+            Self::Unprocessable { .. } => "unprocessable",
+            Self::Invalid { .. } => "invalid",
+            Self::TooLarge { .. } => "too_large",
+            Self::Internal { .. } => "internal",
+            Self::Unavailable { .. } => "unavailable",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::BadRequest { message }
+            | Self::NotFound { message }
+            | Self::Conflict { message }
+            | Self::VersionConflict { message, .. }
+            | Self::Unprocessable { message }
+            | Self::Invalid { message, .. }
+            | Self::TooLarge { message }
+            | Self::Internal { message }
+            | Self::Unavailable { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.code(), self.status(), self.message())?;
+        if let Self::Invalid { fields, .. } = self {
+            for field in fields {
+                write!(f, ", {}", field)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
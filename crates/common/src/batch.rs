@@ -0,0 +1,66 @@
+use crate::{NewToDo, ToDo, ToDoPatch};
+use serde::{Deserialize, Serialize};
+
+/// Operations one batch may hold
+pub const MAX_BATCH_OPS: usize = 100;
+
+/// One operation of a `Batch`, tagged by `op` in JSON
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchOp {
+    Create {
+        todo: NewToDo,
+    },
+    Update {
+        id: usize,
+        patch: ToDoPatch,
+    },
+    /// Moves the todo to the trash, only while it has `version` when set
+    Delete {
+        id: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<u64>,
+    },
+}
+
+/// Payload of `POST /todos/batch`. The operations are applied in order and
+/// all or none of them are
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Batch {
+    pub ops: Vec<BatchOp>,
+}
+
+impl Batch {
+    /// Number of todos the batch creates
+    pub fn creates(&self) -> usize {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op, BatchOp::Create { .. }))
+            .count()
+    }
+
+    /// Ids of the stored todos the batch updates or deletes
+    pub fn targets(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                BatchOp::Create { .. } => None,
+                BatchOp::Update { id, .. } | BatchOp::Delete { id, .. } => Some(*id),
+            })
+            .collect();
+        ids.sort();
+        ids.dedup();
+
+        ids
+    }
+}
+
+/// Outcome of the `BatchOp` at the same position, tagged by `op` in JSON
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BatchResult {
+    Create { todo: ToDo },
+    Update { todo: ToDo },
+    Delete { id: usize },
+}
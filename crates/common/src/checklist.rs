@@ -0,0 +1,265 @@
+use crate::ToDo;
+use serde::{Deserialize, Serialize};
+
+/// Step of a todo, ids are unique within their todo
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct ChecklistItem {
+    pub id: usize,
+    pub text: String,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// Payload adding an item at the end of the checklist
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct NewChecklistItem {
+    pub text: String,
+}
+
+/// Partial update of a `ChecklistItem`, only the fields sent are changed
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChecklistItemPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub done: Option<bool>,
+}
+
+/// Payload reordering a checklist, `ids` holds every item id once
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct ChecklistOrder {
+    pub ids: Vec<usize>,
+}
+
+impl ToDo {
+    /// Items done and items in total, `None` without a checklist
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.checklist.is_empty() {
+            return None;
+        }
+        let done = self.checklist.iter().filter(|item| item.done).count();
+
+        Some((done, self.checklist.len()))
+    }
+
+    /// Checklist with `text` appended
+    pub fn with_item(&self, text: String) -> Vec<ChecklistItem> {
+        let id = self.checklist.iter().map(|item| item.id).max().unwrap_or(0) + 1;
+        let mut checklist = self.checklist.clone();
+        checklist.push(ChecklistItem {
+            id,
+            text,
+            done: false,
+        });
+
+        checklist
+    }
+
+    /// Checklist with item `id` patched, `None` when there is no such item
+    pub fn with_item_patched(
+        &self,
+        id: usize,
+        patch: ChecklistItemPatch,
+    ) -> Option<Vec<ChecklistItem>> {
+        let mut checklist = self.checklist.clone();
+        let item = checklist.iter_mut().find(|item| item.id == id)?;
+        if let Some(text) = patch.text {
+            item.text = text;
+        }
+        if let Some(done) = patch.done {
+            item.done = done;
+        }
+
+        Some(checklist)
+    }
+
+    /// Checklist without item `id`, `None` when there is no such item
+    pub fn without_item(&self, id: usize) -> Option<Vec<ChecklistItem>> {
+        let checklist: Vec<ChecklistItem> = self
+            .checklist
+            .iter()
+            .filter(|item| item.id != id)
+            .cloned()
+            .collect();
+
+        (checklist.len() < self.checklist.len()).then_some(checklist)
+    }
+
+    /// Checklist in the order of `ids`, `None` unless `ids` lists every item
+    /// exactly once
+    pub fn reordered(&self, ids: &[usize]) -> Option<Vec<ChecklistItem>> {
+        if ids.len() != self.checklist.len() {
+            return None;
+        }
+        let mut remaining = self.checklist.clone();
+        let mut checklist = Vec::with_capacity(ids.len());
+        for id in ids {
+            let position = remaining.iter().position(|item| item.id == *id)?;
+            checklist.push(remaining.swap_remove(position));
+        }
+
+        Some(checklist)
+    }
+
+    /// With `auto_complete`, a todo is completed exactly when every item of
+    /// its checklist is done
+    pub fn sync_completion(&mut self) {
+        if let Some((done, total)) = self.progress().filter(|_| self.auto_complete) {
+            self.set_completed(done == total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToDoPatch;
+
+    /// Todo with items `a`, `b` and `c`, ids 1 to 3
+    fn todo() -> ToDo {
+        let mut todo = ToDo::new("Pack for the trip", 1);
+        for text in ["a", "b", "c"] {
+            todo.checklist = todo.with_item(text.to_string());
+        }
+        todo
+    }
+
+    fn ids(checklist: &[ChecklistItem]) -> Vec<usize> {
+        checklist.iter().map(|item| item.id).collect()
+    }
+
+    fn done(done: bool) -> ChecklistItemPatch {
+        ChecklistItemPatch {
+            done: Some(done),
+            ..ChecklistItemPatch::default()
+        }
+    }
+
+    /// Applies `checklist` like the checklist handlers do
+    fn save(todo: &mut ToDo, checklist: Option<Vec<ChecklistItem>>) {
+        todo.apply(ToDoPatch {
+            checklist,
+            ..ToDoPatch::default()
+        });
+    }
+
+    #[test]
+    fn adds_items_with_fresh_ids() {
+        let todo = todo();
+        assert_eq!(ids(&todo.checklist), [1, 2, 3]);
+        assert!(todo.checklist.iter().all(|item| !item.done));
+
+        let checklist = todo.without_item(2).unwrap();
+        let todo = ToDo { checklist, ..todo };
+        let checklist = todo.with_item("d".to_string());
+        assert_eq!(ids(&checklist), [1, 3, 4]);
+        assert_eq!(checklist[2].text, "d");
+    }
+
+    #[test]
+    fn patches_items_by_id() {
+        let todo = todo();
+        let checklist = todo.with_item_patched(2, done(true)).unwrap();
+        let toggled: Vec<bool> = checklist.iter().map(|item| item.done).collect();
+        assert_eq!(toggled, [false, true, false]);
+
+        let renamed = ChecklistItemPatch {
+            text: Some("B".to_string()),
+            ..ChecklistItemPatch::default()
+        };
+        let checklist = todo.with_item_patched(2, renamed).unwrap();
+        assert_eq!(checklist[1].text, "B");
+        assert!(!checklist[1].done);
+
+        assert_eq!(todo.with_item_patched(4, done(true)), None);
+    }
+
+    #[test]
+    fn removes_items_by_id() {
+        let todo = todo();
+        assert_eq!(
+            todo.without_item(1).map(|checklist| ids(&checklist)),
+            Some(vec![2, 3])
+        );
+        assert_eq!(todo.without_item(4), None);
+    }
+
+    #[test]
+    fn reorders_items_by_id() {
+        let todo = todo();
+        let checklist = todo.reordered(&[3, 1, 2]).unwrap();
+        assert_eq!(ids(&checklist), [3, 1, 2]);
+        let texts: Vec<&str> = checklist.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(texts, ["c", "a", "b"]);
+
+        for order in [&[1, 2][..], &[1, 2, 3, 4], &[1, 1, 2], &[1, 2, 4]] {
+            assert_eq!(todo.reordered(order), None, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn counts_progress() {
+        assert_eq!(ToDo::new("Buy milk", 1).progress(), None);
+
+        let mut todo = todo();
+        assert_eq!(todo.progress(), Some((0, 3)));
+        todo.checklist = todo.with_item_patched(3, done(true)).unwrap();
+        assert_eq!(todo.progress(), Some((1, 3)));
+    }
+
+    #[test]
+    fn auto_complete_follows_the_checklist() {
+        let mut todo = ToDo {
+            auto_complete: true,
+            ..todo()
+        };
+        for id in [1, 2] {
+            let checklist = todo.with_item_patched(id, done(true));
+            save(&mut todo, checklist);
+        }
+        assert!(!todo.completed);
+
+        // Checking the last item completes the todo
+        let checklist = todo.with_item_patched(3, done(true));
+        save(&mut todo, checklist);
+        assert!(todo.completed);
+        assert!(todo.completed_at.is_some());
+
+        // Unchecking one reopens it
+        let checklist = todo.with_item_patched(2, done(false));
+        save(&mut todo, checklist);
+        assert!(!todo.completed);
+        assert_eq!(todo.completed_at, None);
+
+        // So does adding an item to a completed one
+        let checklist = todo.with_item_patched(2, done(true));
+        save(&mut todo, checklist);
+        let checklist = Some(todo.with_item("d".to_string()));
+        save(&mut todo, checklist);
+        assert!(!todo.completed);
+    }
+
+    #[test]
+    fn completion_is_manual_without_auto_complete() {
+        let mut todo = todo();
+        for id in [1, 2, 3] {
+            let checklist = todo.with_item_patched(id, done(true));
+            save(&mut todo, checklist);
+        }
+        assert!(!todo.completed);
+
+        // Turning it on catches up with the checklist
+        todo.apply(ToDoPatch {
+            auto_complete: Some(true),
+            ..ToDoPatch::default()
+        });
+        assert!(todo.completed);
+
+        // Reopening by hand wins over the checklist
+        todo.apply(ToDoPatch {
+            checklist: Some(todo.checklist.clone()),
+            ..ToDoPatch::completed(false)
+        });
+        assert!(!todo.completed);
+    }
+}
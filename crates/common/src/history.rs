@@ -0,0 +1,346 @@
+use crate::{ToDo, ToDoPatch, schema};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What made a `Revision`
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+impl Change {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+            Self::Restored => "restored",
+        }
+    }
+}
+
+/// Copy of a todo as it was right after a change. `rev` counts up from 1 per
+/// todo and is never reused, even once older revisions are dropped
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Revision {
+    pub rev: u64,
+    pub at: DateTime<Utc>,
+    pub change: Change,
+    pub todo: ToDo,
+}
+
+impl Revision {
+    /// Reads a stored revision, its todo is upgraded like `ToDo::from_json`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if let Some(todo) = value.get_mut("todo") {
+            schema::upgrade(todo).map_err(serde::de::Error::custom)?;
+        }
+
+        serde_json::from_value(value)
+    }
+
+    /// JSON to store, its todo is stamped like `ToDo::to_json`
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(todo) = value.get_mut("todo") {
+            schema::stamp(todo);
+        }
+
+        serde_json::to_string(&value)
+    }
+
+    /// Patch bringing a todo back to this revision, its list aside
+    pub fn revert_patch(&self) -> ToDoPatch {
+        ToDoPatch {
+            list_id: None,
+            checklist: Some(self.todo.checklist.clone()),
+            completed: Some(self.todo.completed),
+            ..ToDoPatch::from(&self.todo)
+        }
+    }
+}
+
+/// Field other than the text that differs between two revisions, rendered
+/// for display
+#[derive(PartialEq, Debug, Clone)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// Every field but `todo_info` and the timestamps kept up to date by the
+/// server that differs from `before` to `after`, see `diff_lines` for the text
+pub fn changes(before: &ToDo, after: &ToDo) -> Vec<FieldChange> {
+    let date = |date: Option<DateTime<Utc>>| {
+        date.map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    };
+    let checklist = |todo: &ToDo| {
+        todo.checklist
+            .iter()
+            .map(|item| format!("[{}] {}", if item.done { "x" } else { " " }, item.text))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let fields = [
+        (
+            "list_id",
+            before.list_id.to_string(),
+            after.list_id.to_string(),
+        ),
+        ("due_at", date(before.due_at), date(after.due_at)),
+        (
+            "priority",
+            before.priority.as_str().to_string(),
+            after.priority.as_str().to_string(),
+        ),
+        ("tags", before.tags.join(", "), after.tags.join(", ")),
+        ("checklist", checklist(before), checklist(after)),
+        (
+            "auto_complete",
+            before.auto_complete.to_string(),
+            after.auto_complete.to_string(),
+        ),
+        (
+            "recurrence",
+            before
+                .recurrence
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            after
+                .recurrence
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        ),
+        (
+            "completed",
+            before.completed.to_string(),
+            after.completed.to_string(),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(field, before, after)| FieldChange {
+            field,
+            before,
+            after,
+        })
+        .collect()
+}
+
+/// Line of a text diff
+#[derive(PartialEq, Debug, Clone)]
+pub enum LineDiff {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Lines turning `before` into `after`, along their longest common
+/// subsequence
+pub fn diff_lines(before: &str, after: &str) -> Vec<LineDiff> {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Lines kept at both ends are left out of the table, usually all but a
+    // few lines of a note
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let same = |lines: &[&str]| {
+        lines
+            .iter()
+            .map(|line| LineDiff::Same(line.to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut lines = same(&old[..prefix]);
+    lines.extend(lcs_diff(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    ));
+    lines.extend(same(&old[old.len() - suffix..]));
+
+    lines
+}
+
+fn lcs_diff(old: &[&str], new: &[&str]) -> Vec<LineDiff> {
+    // lcs[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = vec![];
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            lines.push(LineDiff::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(LineDiff::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(LineDiff::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        old[i..]
+            .iter()
+            .map(|line| LineDiff::Removed(line.to_string())),
+    );
+    lines.extend(
+        new[j..]
+            .iter()
+            .map(|line| LineDiff::Added(line.to_string())),
+    );
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+    use LineDiff::{Added, Removed, Same};
+
+    fn diff(before: &str, after: &str) -> Vec<LineDiff> {
+        let lines = diff_lines(before, after);
+        // Both texts can be read back from the diff
+        let side = |keep: fn(&LineDiff) -> Option<&String>| {
+            lines.iter().filter_map(keep).cloned().collect::<Vec<_>>()
+        };
+        let old = side(|line| match line {
+            Same(line) | Removed(line) => Some(line),
+            Added(_) => None,
+        });
+        let new = side(|line| match line {
+            Same(line) | Added(line) => Some(line),
+            Removed(_) => None,
+        });
+        assert_eq!(old, before.lines().collect::<Vec<_>>());
+        assert_eq!(new, after.lines().collect::<Vec<_>>());
+
+        lines
+    }
+
+    fn same(line: &str) -> LineDiff {
+        Same(line.to_string())
+    }
+
+    fn added(line: &str) -> LineDiff {
+        Added(line.to_string())
+    }
+
+    fn removed(line: &str) -> LineDiff {
+        Removed(line.to_string())
+    }
+
+    #[test]
+    fn diffs_empty_texts() {
+        assert_eq!(diff("", ""), []);
+        assert_eq!(diff("", "a\nb"), [added("a"), added("b")]);
+        assert_eq!(diff("a\nb", ""), [removed("a"), removed("b")]);
+    }
+
+    #[test]
+    fn unchanged_text_is_the_same() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nb\nc"),
+            [same("a"), same("b"), same("c")]
+        );
+    }
+
+    #[test]
+    fn diffs_insertions_and_deletions() {
+        assert_eq!(diff("a\nc", "a\nb\nc"), [same("a"), added("b"), same("c")]);
+        assert_eq!(
+            diff("a\nb\nc", "a\nc"),
+            [same("a"), removed("b"), same("c")]
+        );
+        assert_eq!(diff("b", "a\nb"), [added("a"), same("b")]);
+        assert_eq!(diff("a\nb", "a"), [same("a"), removed("b")]);
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\nc"),
+            [same("a"), removed("b"), added("x"), same("c")]
+        );
+    }
+
+    #[test]
+    fn diffs_reordered_lines() {
+        assert_eq!(
+            diff("a\nb\nc", "c\na\nb"),
+            [added("c"), same("a"), same("b"), removed("c")]
+        );
+        assert_eq!(
+            diff("x\na\nb\ny", "x\nb\na\ny"),
+            [same("x"), removed("a"), same("b"), added("a"), same("y")]
+        );
+    }
+
+    #[test]
+    fn diffs_repeated_lines() {
+        assert_eq!(diff("a\na", "a\na\na"), [same("a"), same("a"), added("a")]);
+        assert_eq!(
+            diff("a\nb\na", "a"),
+            [same("a"), removed("b"), removed("a")]
+        );
+    }
+
+    #[test]
+    fn lists_changed_fields() {
+        let before = ToDo::new("Buy milk", 1);
+        assert_eq!(changes(&before, &before), []);
+
+        let mut after = before.clone();
+        after.todo_info = "Buy oat milk".to_string();
+        after.version += 1;
+        assert_eq!(changes(&before, &after), []);
+
+        after.priority = Priority::High;
+        after.tags = vec!["home".to_string(), "shop".to_string()];
+        after.set_completed(true);
+        assert_eq!(
+            changes(&before, &after),
+            [
+                FieldChange {
+                    field: "priority",
+                    before: "none".to_string(),
+                    after: "high".to_string(),
+                },
+                FieldChange {
+                    field: "tags",
+                    before: String::new(),
+                    after: "home, shop".to_string(),
+                },
+                FieldChange {
+                    field: "completed",
+                    before: "false".to_string(),
+                    after: "true".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -1,21 +1,337 @@
-use serde::{Deserialize, Serialize};
+mod api_error;
+pub mod batch;
+pub mod checklist;
+pub mod history;
+pub mod lists;
+pub mod query;
+pub mod recurrence;
+pub mod schema;
+pub mod search;
+pub mod tags;
+pub mod validation;
+
+pub use api_error::ApiError;
+use checklist::ChecklistItem;
+use chrono::{DateTime, Utc};
+use recurrence::Recurrence;
+use serde::{Deserialize, Deserializer, Serialize};
 use yew::prelude::*;
 
-pub const UNABLE_TO_PARSE_DATA: &'static str = "Unable to parse data";
+pub const UNABLE_TO_PARSE_DATA: &str = "Unable to parse data";
+/// Format of the `todo_date` string stored before timestamps were typed
+pub const LEGACY_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Urgency of a todo, ordered from `None` to `Urgent`
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Self::None,
+        Self::Low,
+        Self::Medium,
+        Self::High,
+        Self::Urgent,
+    ];
+
+    /// Same name as in JSON
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Urgent => "urgent",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.as_str() == name)
+    }
+}
 
 #[derive(PartialEq, Debug, Properties, Serialize, Deserialize, Clone)]
 pub struct ToDo {
     pub id: usize,
+    /// `lists::INBOX_LIST_ID` for todos filed nowhere else
+    #[serde(default)]
+    pub list_id: usize,
     pub todo_info: String,
-    pub todo_date: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Normalized, sorted and without duplicates, see `tags::normalize_tags`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Ordered steps of the todo
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<ChecklistItem>,
+    /// Completes the todo once every checklist item is done
+    #[serde(default)]
+    pub auto_complete: bool,
+    /// Completing the todo creates its next occurrence, which takes the rule
+    /// over
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub completed: bool,
+    /// When the todo was last marked as completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the todo was moved to the trash, `None` while it is not in there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Bumped by every stored change, sent as the ETag of the todo. Records
+    /// from before versions existed are version 0
+    #[serde(default)]
+    pub version: u64,
 }
 
 impl ToDo {
-    pub fn new(todo_info: &str, todo_date: &str, id: usize) -> Self {
+    pub fn new(todo_info: &str, id: usize) -> Self {
+        let now = Utc::now();
         Self {
             id,
+            list_id: lists::INBOX_LIST_ID,
             todo_info: todo_info.to_string(),
-            todo_date: todo_date.to_string(),
+            created_at: now,
+            updated_at: now,
+            due_at: None,
+            priority: Priority::None,
+            tags: vec![],
+            checklist: vec![],
+            auto_complete: false,
+            recurrence: None,
+            completed: false,
+            completed_at: None,
+            deleted_at: None,
+            version: 1,
+        }
+    }
+
+    /// Reads a stored `ToDo`, records of older schema versions are upgraded
+    /// first, see `schema::upgrade`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        schema::upgrade(&mut value).map_err(serde::de::Error::custom)?;
+
+        serde_json::from_value(value)
+    }
+
+    /// JSON to store, stamped with `schema::SCHEMA_VERSION`
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        schema::stamp(&mut value);
+
+        serde_json::to_string(&value)
+    }
+
+    /// Completing stamps `completed_at`, reopening clears it
+    pub fn set_completed(&mut self, completed: bool) {
+        if completed != self.completed {
+            self.completed = completed;
+            self.completed_at = completed.then(Utc::now);
+        }
+    }
+
+    /// Trashing stamps `deleted_at`, restoring clears it. Both are changes of
+    /// their own and bump the version
+    pub fn set_deleted(&mut self, deleted: bool) {
+        self.deleted_at = deleted.then(Utc::now);
+        self.version += 1;
+    }
+
+    /// Fails with a version conflict carrying this todo unless it has version
+    /// `expected`, anything goes without one
+    pub fn check_version(&self, expected: Option<u64>) -> Result<(), ApiError> {
+        match expected {
+            Some(expected) if expected != self.version => {
+                Err(ApiError::version_conflict(self.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Merge the fields that are set in `patch`, leaving the others untouched.
+    /// `patch.version` is not checked here, see `check_version`
+    pub fn apply(&mut self, patch: ToDoPatch) {
+        if let Some(list_id) = patch.list_id {
+            self.list_id = list_id;
+        }
+        if let Some(todo_info) = patch.todo_info {
+            self.todo_info = todo_info;
+        }
+        if let Some(due_at) = patch.due_at {
+            self.due_at = due_at;
+        }
+        if let Some(priority) = patch.priority {
+            self.priority = priority;
+        }
+        if let Some(tags) = patch.tags {
+            self.tags = tags::normalize_tags(&tags);
+        }
+        let steps_changed = patch.checklist.is_some()
+            || patch
+                .auto_complete
+                .is_some_and(|auto_complete| auto_complete != self.auto_complete);
+        if let Some(checklist) = patch.checklist {
+            self.checklist = checklist;
+        }
+        if let Some(auto_complete) = patch.auto_complete {
+            self.auto_complete = auto_complete;
+        }
+        if let Some(recurrence) = patch.recurrence {
+            self.recurrence = recurrence;
+        }
+        if steps_changed {
+            self.sync_completion();
+        }
+        // Completing or reopening by hand wins over the checklist
+        if let Some(completed) = patch.completed {
+            self.set_completed(completed);
+        }
+        self.updated_at = Utc::now();
+        self.version += 1;
+    }
+}
+
+/// Payload of a create request, the id and timestamps are always assigned by
+/// the server
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NewToDo {
+    #[serde(default)]
+    pub list_id: usize,
+    pub todo_info: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checklist: Vec<ChecklistItem>,
+    #[serde(default)]
+    pub auto_complete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+}
+
+impl NewToDo {
+    pub fn into_todo(self, id: usize) -> ToDo {
+        ToDo {
+            list_id: self.list_id,
+            due_at: self.due_at,
+            priority: self.priority,
+            tags: tags::normalize_tags(&self.tags),
+            checklist: self.checklist,
+            auto_complete: self.auto_complete,
+            recurrence: self.recurrence,
+            ..ToDo::new(&self.todo_info, id)
+        }
+    }
+}
+
+impl From<&ToDo> for NewToDo {
+    fn from(todo: &ToDo) -> Self {
+        Self {
+            list_id: todo.list_id,
+            todo_info: todo.todo_info.clone(),
+            due_at: todo.due_at,
+            priority: todo.priority,
+            tags: todo.tags.clone(),
+            checklist: todo.checklist.clone(),
+            auto_complete: todo.auto_complete,
+            recurrence: todo.recurrence.clone(),
+        }
+    }
+}
+
+/// Tells a field sent as `null` (`Some(None)`) from a missing one (`None`)
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Partial update of a `ToDo`, only the fields sent are changed
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ToDoPatch {
+    /// Moves the todo to another list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub todo_info: Option<String>,
+    /// `null` removes the due date
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Replaces every tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Replaces the whole checklist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checklist: Option<Vec<ChecklistItem>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_complete: Option<bool>,
+    /// `null` stops the todo from recurring
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recurrence: Option<Option<Recurrence>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    /// Only applied while the todo still has this version, the `If-Match`
+    /// header of a request sets it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+impl ToDoPatch {
+    pub fn completed(completed: bool) -> Self {
+        Self {
+            completed: Some(completed),
+            ..Self::default()
+        }
+    }
+}
+
+/// Edits the text, due date, priority, tags, auto completion and recurrence of
+/// `todo`, moves, checklist items and completion have their own endpoints
+impl From<&ToDo> for ToDoPatch {
+    fn from(todo: &ToDo) -> Self {
+        Self {
+            list_id: None,
+            todo_info: Some(todo.todo_info.clone()),
+            due_at: Some(todo.due_at),
+            priority: Some(todo.priority),
+            tags: Some(todo.tags.clone()),
+            checklist: None,
+            auto_complete: Some(todo.auto_complete),
+            recurrence: Some(todo.recurrence.clone()),
+            completed: None,
+            version: None,
         }
     }
 }
@@ -0,0 +1,109 @@
+use crate::query::{SortBy, SortOrder};
+use serde::{Deserialize, Serialize};
+
+/// List of the todos not filed anywhere else, it always exists
+pub const INBOX_LIST_ID: usize = 0;
+pub const DEFAULT_LIST_COLOUR: &str = "#64748b";
+
+/// Named list of todos, e.g. "Groceries"
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct TaskList {
+    pub id: usize,
+    pub name: String,
+    /// `#rrggbb`
+    pub colour: String,
+    /// Lists are shown by ascending position
+    pub position: usize,
+    /// Default sorting of the todos of the list
+    #[serde(default)]
+    pub sort: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl TaskList {
+    pub fn inbox() -> Self {
+        Self {
+            id: INBOX_LIST_ID,
+            name: "Inbox".to_string(),
+            colour: DEFAULT_LIST_COLOUR.to_string(),
+            position: 0,
+            sort: SortBy::default(),
+            order: SortOrder::default(),
+        }
+    }
+
+    /// Merge the fields that are set in `patch`, leaving the others untouched
+    pub fn apply(&mut self, patch: TaskListPatch) {
+        if let Some(name) = patch.name {
+            self.name = name;
+        }
+        if let Some(colour) = patch.colour {
+            self.colour = colour;
+        }
+        if let Some(position) = patch.position {
+            self.position = position;
+        }
+        if let Some(sort) = patch.sort {
+            self.sort = sort;
+        }
+        if let Some(order) = patch.order {
+            self.order = order;
+        }
+    }
+}
+
+/// `#rrggbb` with hexadecimal digits
+pub fn is_valid_colour(colour: &str) -> bool {
+    colour.len() == 7
+        && colour.starts_with('#')
+        && colour[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Payload of a list creation, new lists go last
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NewTaskList {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+    #[serde(default)]
+    pub sort: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl NewTaskList {
+    pub fn into_list(self, id: usize) -> TaskList {
+        TaskList {
+            id,
+            name: self.name,
+            colour: self
+                .colour
+                .unwrap_or_else(|| DEFAULT_LIST_COLOUR.to_string()),
+            position: id,
+            sort: self.sort,
+            order: self.order,
+        }
+    }
+}
+
+/// Partial update of a `TaskList`, only the fields sent are changed
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TaskListPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SortBy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+}
+
+/// Payload moving a todo to another list
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct MoveToDo {
+    pub list_id: usize,
+}
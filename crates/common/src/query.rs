@@ -0,0 +1,498 @@
+use crate::{ApiError, ToDo, tags};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 200;
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    /// Creation order, ids are handed out incrementally
+    #[default]
+    Id,
+    Created,
+    Updated,
+    /// Todos without a due date come first in ascending order
+    Due,
+    /// From `none` to `urgent` in ascending order
+    Priority,
+}
+
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Query string of the todo listing, e.g.
+/// `?limit=20&text=milk&sort=due&order=desc&cursor=...`
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ListQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page, absent for the first page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Only the todos of this list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list: Option<usize>,
+    /// Only todos created on or after this day (UTC), e.g. `2024-01-31`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    /// Only todos created before this day (UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    /// Case insensitive substring of the note
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Only completed todos when `true`, only open ones when `false`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    /// Comma separated tags, only todos carrying all of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub sort: SortBy,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// Position in a sorted listing: the sort key and id of the last todo returned
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cursor {
+    pub id: usize,
+    pub key: String,
+}
+
+impl Cursor {
+    /// `{id}` when the listing is sorted by id, `{id}:{key}` otherwise
+    pub fn encode(&self) -> String {
+        if self.key.is_empty() {
+            self.id.to_string()
+        } else {
+            format!("{}:{}", self.id, self.key)
+        }
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, ApiError> {
+        let (id, key) = cursor.split_once(':').unwrap_or((cursor, ""));
+        let id = id
+            .parse()
+            .map_err(|_| ApiError::bad_request(format!("Invalid cursor `{}`", cursor)))?;
+
+        Ok(Self {
+            id,
+            key: key.to_string(),
+        })
+    }
+}
+
+/// Fixed width RFC 3339, so that keys sort like the timestamps they hold
+fn sort_key(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+impl ListQuery {
+    /// Same filters and sorting, starting again from the first page
+    pub fn first_page(&self) -> Self {
+        Self {
+            cursor: None,
+            ..self.clone()
+        }
+    }
+
+    /// Requested page size, clamped to `1..=MAX_PAGE_LIMIT`
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    pub fn decode_cursor(&self) -> Result<Option<Cursor>, ApiError> {
+        self.cursor.as_deref().map(Cursor::decode).transpose()
+    }
+
+    pub fn has_filters(&self) -> bool {
+        self.list.is_some()
+            || self.from.is_some()
+            || self.to.is_some()
+            || self.text.is_some()
+            || self.completed.is_some()
+            || self.tag.is_some()
+    }
+
+    /// Normalized tags of the `tag` filter
+    pub fn tags(&self) -> Vec<String> {
+        self.tag
+            .as_deref()
+            .map(tags::parse_tags)
+            .unwrap_or_default()
+    }
+
+    /// Plain id order without filters, stores can page it from their index
+    pub fn is_default_order(&self) -> bool {
+        !self.has_filters() && self.sort == SortBy::Id && self.order == SortOrder::Asc
+    }
+
+    pub fn matches(&self, todo: &ToDo) -> bool {
+        let date = todo.created_at.date_naive();
+
+        self.list.is_none_or(|list| todo.list_id == list)
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date < to)
+            && self
+                .text
+                .as_deref()
+                .is_none_or(|text| todo.todo_info.to_lowercase().contains(&text.to_lowercase()))
+            && self
+                .completed
+                .is_none_or(|completed| todo.completed == completed)
+            && self.tags().iter().all(|tag| todo.tags.contains(tag))
+    }
+
+    fn cursor_of(&self, todo: &ToDo) -> Cursor {
+        let key = match self.sort {
+            SortBy::Id => String::new(),
+            SortBy::Created => sort_key(&todo.created_at),
+            SortBy::Updated => sort_key(&todo.updated_at),
+            SortBy::Due => todo.due_at.as_ref().map(sort_key).unwrap_or_default(),
+            // A single digit, so that keys sort like the priorities
+            SortBy::Priority => (todo.priority as u8).to_string(),
+        };
+
+        Cursor { id: todo.id, key }
+    }
+
+    /// Orders by the sort key, then by id so that the order is stable
+    fn compare(&self, a: &Cursor, b: &Cursor) -> Ordering {
+        let ordering = a.key.cmp(&b.key).then(a.id.cmp(&b.id));
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Filters, sorts and pages `todos`, which must hold every candidate
+    pub fn apply(&self, todos: Vec<ToDo>) -> Result<Page<ToDo>, ApiError> {
+        let after = self.decode_cursor()?;
+        let limit = self.limit();
+
+        let mut todos: Vec<(Cursor, ToDo)> = todos
+            .into_iter()
+            .filter(|todo| self.matches(todo))
+            .map(|todo| (self.cursor_of(&todo), todo))
+            .filter(|(cursor, _)| match &after {
+                Some(after) => self.compare(cursor, after) == Ordering::Greater,
+                None => true,
+            })
+            .collect();
+        todos.sort_by(|(a, _), (b, _)| self.compare(a, b));
+        todos.truncate(limit + 1);
+
+        let page = Page::from_overfetched(todos, limit, |(cursor, _)| cursor.encode());
+        Ok(Page {
+            items: page.items.into_iter().map(|(_, todo)| todo).collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Query string for the listing endpoint, without the leading `?`
+    pub fn to_query_string(&self) -> String {
+        serde_urlencoded::to_string(self).unwrap_or_default()
+    }
+}
+
+/// One page of a listing, `next_cursor` is `None` on the last page
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page out of up to `limit + 1` items, the extra one only tells
+    /// that there is a next page
+    pub fn from_overfetched(
+        mut items: Vec<T>,
+        limit: usize,
+        cursor_of: impl Fn(&T) -> String,
+    ) -> Self {
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(cursor_of)
+        } else {
+            None
+        };
+
+        Self { items, next_cursor }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Priority;
+
+    fn todos(n: usize) -> Vec<ToDo> {
+        (1..=n).map(|id| ToDo::new("Buy milk", id)).collect()
+    }
+
+    /// Ids of every page, following the cursors
+    fn pages(query: &ListQuery, todos: &[ToDo]) -> Vec<Vec<usize>> {
+        let mut query = query.first_page();
+        let mut pages = vec![];
+        loop {
+            let page = query.apply(todos.to_vec()).unwrap();
+            pages.push(page.items.iter().map(|todo| todo.id).collect());
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return pages,
+            }
+        }
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let by_id = Cursor {
+            id: 7,
+            key: String::new(),
+        };
+        assert_eq!(by_id.encode(), "7");
+        assert_eq!(Cursor::decode("7").unwrap(), by_id);
+
+        // Timestamps hold colons themselves
+        let by_date = Cursor {
+            id: 7,
+            key: sort_key(&Utc::now()),
+        };
+        assert_eq!(Cursor::decode(&by_date.encode()).unwrap(), by_date);
+    }
+
+    #[test]
+    fn rejects_garbage_cursors() {
+        for cursor in ["", "abc", ":5", "-1", "1.5", "x:y"] {
+            let error = Cursor::decode(cursor).unwrap_err();
+            assert_eq!(error.status(), 400, "{:?}", cursor);
+        }
+        let query = ListQuery {
+            cursor: Some("abc".to_string()),
+            ..ListQuery::default()
+        };
+        assert!(query.apply(todos(3)).is_err());
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let limit = |limit| ListQuery {
+            limit,
+            ..ListQuery::default()
+        };
+        assert_eq!(limit(None).limit(), DEFAULT_PAGE_LIMIT);
+        assert_eq!(limit(Some(0)).limit(), 1);
+        assert_eq!(limit(Some(20)).limit(), 20);
+        assert_eq!(limit(Some(MAX_PAGE_LIMIT + 1)).limit(), MAX_PAGE_LIMIT);
+    }
+
+    #[test]
+    fn pages_from_overfetched_items() {
+        let page = |n: usize| Page::from_overfetched((1..=n).collect(), 3, |id| id.to_string());
+
+        let short = page(2);
+        assert_eq!(short.items, [1, 2]);
+        assert_eq!(short.next_cursor, None);
+
+        let exact = page(3);
+        assert_eq!(exact.items, [1, 2, 3]);
+        assert_eq!(exact.next_cursor, None);
+
+        let more = page(4);
+        assert_eq!(more.items, [1, 2, 3]);
+        assert_eq!(more.next_cursor.as_deref(), Some("3"));
+
+        assert_eq!(page(0).items, Vec::<usize>::new());
+    }
+
+    #[test]
+    fn pages_in_id_order() {
+        let query = ListQuery {
+            limit: Some(2),
+            ..ListQuery::default()
+        };
+        let mut shuffled = todos(5);
+        shuffled.reverse();
+        assert_eq!(pages(&query, &shuffled), [vec![1, 2], vec![3, 4], vec![5]]);
+
+        let exact = ListQuery {
+            limit: Some(5),
+            ..ListQuery::default()
+        };
+        assert_eq!(pages(&exact, &shuffled), [vec![1, 2, 3, 4, 5]]);
+        assert_eq!(pages(&query, &[]), [Vec::<usize>::new()]);
+    }
+
+    fn at(day: u32) -> DateTime<Utc> {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap()
+            .and_utc()
+    }
+
+    fn ids(query: &ListQuery, todos: &[ToDo]) -> Vec<usize> {
+        pages(query, todos).concat()
+    }
+
+    /// Todos of many priorities, created on different days, with ties
+    fn sample() -> Vec<ToDo> {
+        let priorities = [
+            Priority::High,
+            Priority::None,
+            Priority::High,
+            Priority::Low,
+            Priority::High,
+            Priority::None,
+            Priority::Urgent,
+        ];
+        priorities
+            .into_iter()
+            .enumerate()
+            .map(|(index, priority)| {
+                let mut todo = ToDo::new("Buy milk", index + 1);
+                todo.priority = priority;
+                todo.created_at = at(7 - index as u32);
+                todo
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ties_break_on_the_id_across_pages() {
+        let todos = sample();
+        for limit in 1..=todos.len() {
+            let by_priority = ListQuery {
+                limit: Some(limit),
+                sort: SortBy::Priority,
+                ..ListQuery::default()
+            };
+            assert_eq!(ids(&by_priority, &todos), [2, 6, 4, 1, 3, 5, 7]);
+
+            let descending = ListQuery {
+                order: SortOrder::Desc,
+                ..by_priority
+            };
+            assert_eq!(ids(&descending, &todos), [7, 5, 3, 1, 4, 6, 2]);
+        }
+    }
+
+    #[test]
+    fn sorts_by_dates() {
+        let mut todos = sample();
+        todos[2].due_at = Some(at(20));
+        todos[4].due_at = Some(at(10));
+        let by_created = ListQuery {
+            limit: Some(3),
+            sort: SortBy::Created,
+            ..ListQuery::default()
+        };
+        assert_eq!(ids(&by_created, &todos), [7, 6, 5, 4, 3, 2, 1]);
+
+        // Todos without a due date come first
+        let by_due = ListQuery {
+            limit: Some(2),
+            sort: SortBy::Due,
+            ..ListQuery::default()
+        };
+        assert_eq!(ids(&by_due, &todos), [1, 2, 4, 6, 7, 5, 3]);
+    }
+
+    #[test]
+    fn filters() {
+        let mut todos = sample();
+        todos[0].list_id = 2;
+        todos[1].todo_info = "Call MOM".to_string();
+        todos[2].set_completed(true);
+        todos[3].tags = vec!["home".to_string(), "work".to_string()];
+        todos[4].tags = vec!["work".to_string()];
+
+        let filtered = |query: ListQuery| ids(&query, &todos);
+        assert_eq!(
+            filtered(ListQuery {
+                list: Some(2),
+                ..ListQuery::default()
+            }),
+            [1]
+        );
+        // Created on the 2nd up to the 4th of January
+        assert_eq!(
+            filtered(ListQuery {
+                from: Some(at(2).date_naive()),
+                to: Some(at(5).date_naive()),
+                ..ListQuery::default()
+            }),
+            [4, 5, 6]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                text: Some("mom".to_string()),
+                ..ListQuery::default()
+            }),
+            [2]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                completed: Some(true),
+                ..ListQuery::default()
+            }),
+            [3]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                completed: Some(false),
+                ..ListQuery::default()
+            }),
+            [1, 2, 4, 5, 6, 7]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                tag: Some("#Work".to_string()),
+                ..ListQuery::default()
+            }),
+            [4, 5]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                tag: Some("work,home".to_string()),
+                ..ListQuery::default()
+            }),
+            [4]
+        );
+        assert_eq!(
+            filtered(ListQuery {
+                text: Some("milk".to_string()),
+                completed: Some(false),
+                tag: Some("work".to_string()),
+                ..ListQuery::default()
+            }),
+            [4, 5]
+        );
+    }
+
+    #[test]
+    fn only_plain_listings_use_the_index_order() {
+        assert!(ListQuery::default().is_default_order());
+        let filtered = ListQuery {
+            completed: Some(true),
+            ..ListQuery::default()
+        };
+        assert!(filtered.has_filters());
+        assert!(!filtered.is_default_order());
+        let descending = ListQuery {
+            order: SortOrder::Desc,
+            ..ListQuery::default()
+        };
+        assert!(!descending.is_default_order());
+    }
+}
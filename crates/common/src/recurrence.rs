@@ -0,0 +1,573 @@
+use crate::checklist::ChecklistItem;
+use crate::{NewToDo, ToDo};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Periods looked at for an occurrence before giving up, e.g. `BYMONTHDAY=30`
+/// every 12 months starting in February never occurs
+const MAX_PERIODS: u32 = 100;
+/// Largest `INTERVAL` accepted
+const MAX_INTERVAL: u32 = 1000;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Daily => "DAILY",
+            Self::Weekly => "WEEKLY",
+            Self::Monthly => "MONTHLY",
+        }
+    }
+}
+
+/// Subset of an iCalendar RRULE, e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH`.
+///
+/// `BYDAY` only goes with `FREQ=WEEKLY` and `BYMONTHDAY` with `FREQ=MONTHLY`.
+/// `COUNT` counts the occurrences left, the current one included. It goes
+/// down by one with each occurrence created
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Days of the week, sorted from Monday, the day of the current
+    /// occurrence when empty
+    pub by_day: Vec<Weekday>,
+    /// Days of the month, negative ones count from the end (`-1` is the last
+    /// day). The day of the current occurrence when empty. Months without the
+    /// day are skipped
+    pub by_month_day: Vec<i32>,
+    pub count: Option<u32>,
+    /// Last day an occurrence may fall on
+    pub until: Option<NaiveDate>,
+}
+
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    match name {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// `YYYYMMDD`, a time part as in `20261231T235959Z` is ignored
+fn parse_until(value: &str) -> Option<NaiveDate> {
+    let date = value.split('T').next()?;
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {} `{}`", name, value))
+}
+
+/// `day` of the month of `first`, `None` when the month is too short
+fn month_day(first: NaiveDate, day: i32) -> Option<NaiveDate> {
+    if day > 0 {
+        return first.with_day(day as u32);
+    }
+    let last = first.checked_add_months(Months::new(1))?.pred_opt()?;
+    let day = last.day() as i32 + day + 1;
+
+    (day >= 1).then(|| first.with_day(day as u32)).flatten()
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let rule = rule.trim();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(rule);
+        let mut frequency = None;
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: vec![],
+            by_month_day: vec![],
+            count: None,
+            until: None,
+        };
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid rule part `{}`", part))?;
+            match name.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("Unsupported frequency `{}`", value)),
+                    })
+                }
+                "INTERVAL" => recurrence.interval = parse_number("interval", value)?,
+                "COUNT" => recurrence.count = Some(parse_number("count", value)?),
+                "UNTIL" => {
+                    recurrence.until = Some(
+                        parse_until(value).ok_or_else(|| format!("Invalid until `{}`", value))?,
+                    )
+                }
+                "BYDAY" => {
+                    recurrence.by_day = value
+                        .split(',')
+                        .map(|day| {
+                            parse_weekday(&day.to_ascii_uppercase())
+                                .ok_or_else(|| format!("Unsupported day `{}`", day))
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => {
+                    recurrence.by_month_day = value
+                        .split(',')
+                        .map(|day| parse_number("day of the month", day))
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(format!("Unsupported rule part `{}`", name)),
+            }
+        }
+
+        recurrence.frequency = frequency.ok_or("FREQ is required")?;
+        if !(1..=MAX_INTERVAL).contains(&recurrence.interval) {
+            return Err(format!("INTERVAL must be between 1 and {}", MAX_INTERVAL));
+        }
+        if recurrence.count == Some(0) {
+            return Err("COUNT must be at least 1".to_string());
+        }
+        if !recurrence.by_day.is_empty() && recurrence.frequency != Frequency::Weekly {
+            return Err("BYDAY needs FREQ=WEEKLY".to_string());
+        }
+        if !recurrence.by_month_day.is_empty() && recurrence.frequency != Frequency::Monthly {
+            return Err("BYMONTHDAY needs FREQ=MONTHLY".to_string());
+        }
+        if let Some(day) = recurrence
+            .by_month_day
+            .iter()
+            .find(|day| !(1..=31).contains(&day.abs()))
+        {
+            return Err(format!("Invalid day of the month `{}`", day));
+        }
+        recurrence
+            .by_day
+            .sort_by_key(|day| day.num_days_from_monday());
+        recurrence.by_day.dedup();
+        recurrence.by_month_day.sort();
+        recurrence.by_month_day.dedup();
+
+        Ok(recurrence)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FREQ={}", self.frequency.as_str())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|day| weekday_name(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if !self.by_month_day.is_empty() {
+            let days: Vec<String> = self.by_month_day.iter().map(i32::to_string).collect();
+            write!(f, ";BYMONTHDAY={}", days.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        rule.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(recurrence: Recurrence) -> Self {
+        recurrence.to_string()
+    }
+}
+
+impl Recurrence {
+    /// First day after `current` matching the rule, ignoring `COUNT` and
+    /// `UNTIL`. Periods are counted from the one of `current`, the search ends
+    /// at the first date out of range
+    fn following_day(&self, current: NaiveDate) -> Option<NaiveDate> {
+        match self.frequency {
+            Frequency::Daily => current.checked_add_days(Days::new(self.interval as u64)),
+            Frequency::Weekly => {
+                let days = if self.by_day.is_empty() {
+                    vec![current.weekday()]
+                } else {
+                    self.by_day.clone()
+                };
+                let monday = current.week(Weekday::Mon).first_day();
+                for period in 0..MAX_PERIODS {
+                    let weeks = period.checked_mul(self.interval)?;
+                    let monday = monday.checked_add_days(Days::new(7 * weeks as u64))?;
+                    let date = days
+                        .iter()
+                        .filter_map(|day| {
+                            monday.checked_add_days(Days::new(day.num_days_from_monday() as u64))
+                        })
+                        .find(|date| *date > current);
+                    if date.is_some() {
+                        return date;
+                    }
+                }
+                None
+            }
+            Frequency::Monthly => {
+                let days = if self.by_month_day.is_empty() {
+                    vec![current.day() as i32]
+                } else {
+                    self.by_month_day.clone()
+                };
+                let first = current.with_day(1)?;
+                for period in 0..MAX_PERIODS {
+                    let months = period.checked_mul(self.interval)?;
+                    let first = first.checked_add_months(Months::new(months))?;
+                    let date = days
+                        .iter()
+                        .filter_map(|day| month_day(first, *day))
+                        .filter(|date| *date > current)
+                        .min();
+                    if date.is_some() {
+                        return date;
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// Day of the occurrence after `current` with the rule it carries, `None`
+    /// once `COUNT` or `UNTIL` ends the series
+    pub fn next(&self, current: NaiveDate) -> Option<(NaiveDate, Recurrence)> {
+        if self.count.is_some_and(|count| count <= 1) {
+            return None;
+        }
+        let date = self
+            .following_day(current)
+            .filter(|date| self.until.is_none_or(|until| *date <= until))?;
+        let recurrence = Recurrence {
+            count: self.count.map(|count| count - 1),
+            ..self.clone()
+        };
+
+        Some((date, recurrence))
+    }
+}
+
+impl ToDo {
+    /// Next occurrence of a recurring todo, due on the next day of its rule at
+    /// the same time. Todos without a due date recur from their creation
+    pub fn next_occurrence(&self) -> Option<NewToDo> {
+        let recurrence = self.recurrence.as_ref()?;
+        let current = self.due_at.unwrap_or(self.created_at);
+        let (date, recurrence) = recurrence.next(current.date_naive())?;
+
+        Some(NewToDo {
+            due_at: Some(date.and_time(current.time()).and_utc()),
+            checklist: self
+                .checklist
+                .iter()
+                .map(|item| ChecklistItem {
+                    done: false,
+                    ..item.clone()
+                })
+                .collect(),
+            recurrence: Some(recurrence),
+            ..NewToDo::from(self)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    /// The days of the `n` occurrences after `start`
+    fn occurrences(rule: &Recurrence, start: NaiveDate, n: usize) -> Vec<NaiveDate> {
+        let mut current = (start, rule.clone());
+        let mut dates = vec![];
+        while dates.len() < n {
+            let Some(next) = current.1.next(current.0) else {
+                break;
+            };
+            dates.push(next.0);
+            current = next;
+        }
+
+        dates
+    }
+
+    #[test]
+    fn parses_and_prints_rules() {
+        let recurrence = rule("RRULE:freq=weekly;INTERVAL=2;BYDAY=TH,MO,TH;COUNT=5");
+        assert_eq!(recurrence.frequency, Frequency::Weekly);
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.by_day, vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(recurrence.count, Some(5));
+        assert_eq!(
+            recurrence.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=5"
+        );
+        assert_eq!(
+            rule("FREQ=MONTHLY;BYMONTHDAY=-1,1;UNTIL=20261231T235959Z").to_string(),
+            "FREQ=MONTHLY;BYMONTHDAY=-1,1;UNTIL=20261231"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        for rule in [
+            "",
+            "INTERVAL=2",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=MONTHLY;INTERVAL=50000000",
+            "FREQ=DAILY;COUNT=0",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=1MO",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=MONTHLY;BYMONTHDAY=0",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=DAILY;BYSETPOS=1",
+            "FREQ=DAILY;UNTIL=tomorrow",
+        ] {
+            assert!(rule.parse::<Recurrence>().is_err(), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn daily_with_interval() {
+        assert_eq!(
+            occurrences(&rule("FREQ=DAILY;INTERVAL=3"), date(2026, 12, 30), 2),
+            vec![date(2027, 1, 2), date(2027, 1, 5)]
+        );
+    }
+
+    #[test]
+    fn weekly_on_weekdays() {
+        // 2026-10-14 is a Wednesday
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY;BYDAY=MO,FR"), date(2026, 10, 14), 3),
+            vec![date(2026, 10, 16), date(2026, 10, 19), date(2026, 10, 23)]
+        );
+        assert_eq!(
+            occurrences(
+                &rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE"),
+                date(2026, 10, 12),
+                3
+            ),
+            vec![date(2026, 10, 14), date(2026, 10, 26), date(2026, 10, 28)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY"), date(2026, 10, 14), 1),
+            vec![date(2026, 10, 21)]
+        );
+    }
+
+    #[test]
+    fn weekly_across_the_end_of_the_year() {
+        // 2026-12-31 is a Thursday
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY;BYDAY=MO"), date(2026, 12, 31), 1),
+            vec![date(2027, 1, 4)]
+        );
+    }
+
+    #[test]
+    fn monthly_skips_months_without_the_day() {
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=31"), date(2026, 1, 31), 3),
+            vec![date(2026, 3, 31), date(2026, 5, 31), date(2026, 7, 31)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY"), date(2026, 8, 31), 2),
+            vec![date(2026, 10, 31), date(2026, 12, 31)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=30"), date(2026, 1, 30), 1),
+            vec![date(2026, 3, 30)]
+        );
+    }
+
+    #[test]
+    fn monthly_on_the_last_day() {
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=-1"), date(2026, 1, 31), 4),
+            vec![
+                date(2026, 2, 28),
+                date(2026, 3, 31),
+                date(2026, 4, 30),
+                date(2026, 5, 31)
+            ]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=1,-1"), date(2026, 4, 1), 3),
+            vec![date(2026, 4, 30), date(2026, 5, 1), date(2026, 5, 31)]
+        );
+    }
+
+    #[test]
+    fn leap_years() {
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=-1"), date(2028, 1, 31), 1),
+            vec![date(2028, 2, 29)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=29"), date(2027, 1, 29), 1),
+            vec![date(2027, 3, 29)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYMONTHDAY=29"), date(2028, 1, 29), 1),
+            vec![date(2028, 2, 29)]
+        );
+        // Only February of leap years has a 29th every 12 months
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;INTERVAL=12"), date(2024, 2, 29), 2),
+            vec![date(2028, 2, 29), date(2032, 2, 29)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=DAILY"), date(2028, 2, 28), 2),
+            vec![date(2028, 2, 29), date(2028, 3, 1)]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=DAILY"), date(2100, 2, 28), 1),
+            vec![date(2100, 3, 1)]
+        );
+    }
+
+    #[test]
+    fn never_occurring_rules_end() {
+        assert_eq!(
+            occurrences(
+                &rule("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=30"),
+                date(2026, 2, 1),
+                1
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn huge_intervals_end_without_overflow() {
+        assert!(
+            rule("FREQ=MONTHLY;INTERVAL=1000")
+                .next(date(2026, 1, 15))
+                .is_some()
+        );
+        // Only the second period has an occurrence, and it is out of range
+        for rule in [
+            rule("FREQ=DAILY"),
+            rule("FREQ=WEEKLY"),
+            rule("FREQ=MONTHLY;BYMONTHDAY=14"),
+        ] {
+            let recurrence = Recurrence {
+                interval: u32::MAX,
+                ..rule
+            };
+            assert_eq!(recurrence.next(date(2026, 1, 15)), None, "{}", recurrence);
+        }
+    }
+
+    #[test]
+    fn count_and_until_end_the_series() {
+        let dates = occurrences(&rule("FREQ=DAILY;COUNT=3"), date(2026, 10, 1), 10);
+        assert_eq!(dates, vec![date(2026, 10, 2), date(2026, 10, 3)]);
+
+        let dates = occurrences(
+            &rule("FREQ=WEEKLY;BYDAY=MO;UNTIL=20261019"),
+            date(2026, 10, 1),
+            10,
+        );
+        assert_eq!(
+            dates,
+            vec![date(2026, 10, 5), date(2026, 10, 12), date(2026, 10, 19)]
+        );
+
+        let (_, next) = rule("FREQ=DAILY;COUNT=3").next(date(2026, 10, 1)).unwrap();
+        assert_eq!(next.count, Some(2));
+    }
+
+    #[test]
+    fn next_occurrence_of_a_todo() {
+        let mut todo = ToDo::new("Water the plants", 1);
+        todo.due_at = Some(date(2026, 10, 31).and_hms_opt(18, 30, 0).unwrap().and_utc());
+        todo.recurrence = Some(rule("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=2"));
+        todo.checklist = todo.with_item("Balcony".to_string());
+        todo.checklist[0].done = true;
+        todo.set_completed(true);
+
+        let next = todo.next_occurrence().unwrap();
+        assert_eq!(
+            next.due_at,
+            Some(date(2026, 11, 30).and_hms_opt(18, 30, 0).unwrap().and_utc())
+        );
+        assert_eq!(
+            next.recurrence,
+            Some(rule("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=1"))
+        );
+        assert!(!next.checklist[0].done);
+
+        todo.recurrence = next.recurrence;
+        assert_eq!(todo.next_occurrence(), None);
+    }
+
+    #[test]
+    fn rules_round_trip_through_json() {
+        let recurrence = rule("FREQ=WEEKLY;BYDAY=SA");
+        let json = serde_json::to_string(&recurrence).unwrap();
+        assert_eq!(json, "\"FREQ=WEEKLY;BYDAY=SA\"");
+        assert_eq!(
+            serde_json::from_str::<Recurrence>(&json).unwrap(),
+            recurrence
+        );
+        assert!(serde_json::from_str::<Recurrence>("\"FREQ=HOURLY\"").is_err());
+    }
+}
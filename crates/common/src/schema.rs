@@ -0,0 +1,129 @@
+use crate::LEGACY_DATE_FORMAT;
+use chrono::NaiveDateTime;
+use serde_json::{Map, Value};
+
+/// Version of the JSON a `ToDo` is stored as, bump it together with a new
+/// step in `UPGRADES`. Fields with a serde default need no step
+pub const SCHEMA_VERSION: u64 = 1;
+/// Field of the stored JSON holding its version
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+type Upgrade = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `UPGRADES[n]` turns a version `n` record into a version `n + 1` one
+const UPGRADES: [Upgrade; SCHEMA_VERSION as usize] = [typed_timestamps];
+
+/// Version of a stored record. Records written before versioning have none,
+/// they are version 1 once they have typed timestamps
+pub fn version_of(fields: &Map<String, Value>) -> u64 {
+    match fields.get(SCHEMA_VERSION_FIELD).and_then(Value::as_u64) {
+        Some(version) => version,
+        None if fields.contains_key("created_at") => 1,
+        None => 0,
+    }
+}
+
+/// Version 0 kept a `todo_date` string, version 1 has typed `created_at` and
+/// `updated_at` timestamps instead
+fn typed_timestamps(fields: &mut Map<String, Value>) -> Result<(), String> {
+    let Some(Value::String(date)) = fields.remove("todo_date") else {
+        return Err("`todo_date` is missing".to_string());
+    };
+    let date = NaiveDateTime::parse_from_str(&date, LEGACY_DATE_FORMAT)
+        .map_err(|e| e.to_string())?
+        .and_utc();
+    let date = serde_json::to_value(date).map_err(|e| e.to_string())?;
+    fields.insert("created_at".into(), date.clone());
+    fields.insert("updated_at".into(), date);
+
+    Ok(())
+}
+
+/// Runs the upgrades `value` misses, records of a newer version are left
+/// untouched. The version field is removed
+pub fn upgrade(value: &mut Value) -> Result<(), String> {
+    let fields = value
+        .as_object_mut()
+        .ok_or_else(|| "a ToDo is a JSON object".to_string())?;
+    let version = version_of(fields);
+    fields.remove(SCHEMA_VERSION_FIELD);
+    for upgrade in UPGRADES.iter().skip(version as usize) {
+        upgrade(fields)?;
+    }
+
+    Ok(())
+}
+
+/// Adds the current version to the JSON of a `ToDo`
+pub fn stamp(value: &mut Value) {
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert(SCHEMA_VERSION_FIELD.into(), SCHEMA_VERSION.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToDo;
+    use serde_json::json;
+
+    fn fields(value: &Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn versions_of_records() {
+        assert_eq!(version_of(&fields(&json!({ "todo_date": "" }))), 0);
+        assert_eq!(version_of(&fields(&json!({ "created_at": "" }))), 1);
+        assert_eq!(version_of(&fields(&json!({ "schema_version": 7 }))), 7);
+
+        let mut value = json!({ "id": 1 });
+        stamp(&mut value);
+        assert_eq!(version_of(&fields(&value)), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn upgrades_version_0() {
+        let mut value = json!({
+            "id": 3,
+            "todo_info": "Water the plants",
+            "todo_date": "2023-04-05 06:07:08",
+        });
+        upgrade(&mut value).unwrap();
+        assert_eq!(value.get("todo_date"), None);
+        assert_eq!(value["created_at"], json!("2023-04-05T06:07:08Z"));
+        assert_eq!(value["updated_at"], value["created_at"]);
+
+        let todo = ToDo::from_json(&value.to_string()).unwrap();
+        assert_eq!(todo.id, 3);
+        assert_eq!(
+            todo.created_at.format(LEGACY_DATE_FORMAT).to_string(),
+            "2023-04-05 06:07:08"
+        );
+    }
+
+    #[test]
+    fn current_records_pass_through() {
+        let todo = ToDo::new("Water the plants", 3);
+        let mut value = serde_json::from_str(&todo.to_json().unwrap()).unwrap();
+        assert_eq!(version_of(&fields(&value)), SCHEMA_VERSION);
+
+        upgrade(&mut value).unwrap();
+        assert_eq!(value, serde_json::to_value(&todo).unwrap());
+        assert_eq!(ToDo::from_json(&todo.to_json().unwrap()).unwrap(), todo);
+    }
+
+    #[test]
+    fn refuses_malformed_records() {
+        for mut value in [
+            json!([]),
+            json!({ "id": 3 }),
+            json!({ "id": 3, "todo_date": 20230405 }),
+            json!({ "id": 3, "todo_date": "yesterday" }),
+            json!({ "id": 3, "todo_date": "2023-04-05T06:07:08Z" }),
+        ] {
+            assert!(upgrade(&mut value).is_err(), "{}", value);
+        }
+        assert!(ToDo::from_json(r#"{"id": 3, "todo_date": "yesterday"}"#).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use crate::ToDo;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+pub const DEFAULT_SEARCH_LIMIT: usize = 20;
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// Query string of the search endpoint, e.g. `?q=groceries&limit=10`
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SearchQuery {
+    pub q: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT)
+    }
+
+    /// Query string for the search endpoint, without the leading `?`
+    pub fn to_query_string(&self) -> String {
+        serde_urlencoded::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A `ToDo` matching a search, best hits come first
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct SearchHit {
+    pub todo: ToDo,
+    pub score: f32,
+    /// Byte ranges of `todo.todo_info` that matched, sorted and disjoint
+    pub matches: Vec<Range<usize>>,
+}
@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_TAG_LEN: usize = 32;
+
+/// Canonical form of a tag: lowercase, without a leading `#`, with runs of
+/// whitespace turned into `-` and anything but letters, digits, `-` and `_`
+/// dropped. `None` when nothing is left
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#');
+    let mut normalized = String::new();
+    for word in tag.split_whitespace() {
+        if !normalized.is_empty() {
+            normalized.push('-');
+        }
+        normalized.extend(
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                .flat_map(char::to_lowercase),
+        );
+    }
+    let normalized: String = normalized.chars().take(MAX_TAG_LEN).collect();
+    let normalized = normalized.trim_matches('-');
+
+    (!normalized.is_empty()).then(|| normalized.to_string())
+}
+
+/// Normalizes every tag, then sorts them and drops duplicates
+pub fn normalize_tags<S: AsRef<str>>(tags: &[S]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .filter_map(|tag| normalize_tag(tag.as_ref()))
+        .collect();
+    tags.sort();
+    tags.dedup();
+
+    tags
+}
+
+/// Splits a comma separated list of tags, e.g. typed in a form
+pub fn parse_tags(tags: &str) -> Vec<String> {
+    normalize_tags(&tags.split(',').collect::<Vec<_>>())
+}
+
+/// Entry of the tag listing
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Payload of a tag rename
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct TagRename {
+    pub to: String,
+}
+
+/// Payload of a tag merge, the todos tagged with any of `from` are tagged
+/// with `into` instead
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct TagMerge {
+    pub from: Vec<String>,
+    pub into: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_tags() {
+        assert_eq!(normalize_tag("  #Work ").as_deref(), Some("work"));
+        assert_eq!(
+            normalize_tag("Home  Office").as_deref(),
+            Some("home-office")
+        );
+        assert_eq!(normalize_tag("to_do-list").as_deref(), Some("to_do-list"));
+        assert_eq!(normalize_tag("C++!").as_deref(), Some("c"));
+        assert_eq!(normalize_tag("- x -").as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn folds_case_beyond_ascii() {
+        assert_eq!(normalize_tag("ÉTÉ").as_deref(), Some("été"));
+        assert_eq!(normalize_tag("Straße").as_deref(), Some("straße"));
+    }
+
+    #[test]
+    fn caps_the_length() {
+        let long = "a".repeat(MAX_TAG_LEN + 8);
+        assert_eq!(normalize_tag(&long).map(|tag| tag.len()), Some(MAX_TAG_LEN));
+        // Cut right after a separator
+        let tag = format!("{} b", "a".repeat(MAX_TAG_LEN - 1));
+        assert_eq!(normalize_tag(&tag), Some("a".repeat(MAX_TAG_LEN - 1)));
+    }
+
+    #[test]
+    fn drops_empty_tags() {
+        for tag in ["", "   ", "#", "###", "!?", " - "] {
+            assert_eq!(normalize_tag(tag), None, "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn sorts_and_deduplicates() {
+        assert_eq!(
+            normalize_tags(&["Work", "#work", "home", "WORK ", "", "!"]),
+            ["home", "work"]
+        );
+        assert!(normalize_tags::<&str>(&[]).is_empty());
+    }
+
+    #[test]
+    fn parses_comma_separated_tags() {
+        assert_eq!(
+            parse_tags("Work, home office,, #Urgent,work"),
+            ["home-office", "urgent", "work"]
+        );
+        assert!(parse_tags(" , ").is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+use crate::tags::{MAX_TAG_LEN, normalize_tag};
+use crate::{NewToDo, ToDo, ToDoPatch};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// In characters
+pub const MAX_TODO_INFO_LEN: usize = 10_000;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_CHECKLIST_ITEMS: usize = 100;
+/// In characters
+pub const MAX_ITEM_LEN: usize = 500;
+/// Years a date may fall in, anything else is a typo or garbage
+pub const MIN_YEAR: i32 = 1970;
+pub const MAX_YEAR: i32 = 9999;
+
+/// Reason a field of a `ToDo` is rejected, `field` is its name in JSON
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// First error on `field`, e.g. to show it next to the input
+pub fn error_for<'a>(errors: &'a [FieldError], field: &str) -> Option<&'a str> {
+    errors
+        .iter()
+        .find(|error| error.field == field)
+        .map(|error| error.message.as_str())
+}
+
+fn check_date(errors: &mut Vec<FieldError>, field: &str, date: Option<DateTime<Utc>>) {
+    if date.is_some_and(|date| !(MIN_YEAR..=MAX_YEAR).contains(&date.year())) {
+        errors.push(FieldError::new(
+            field,
+            format!("Must be between {} and {}", MIN_YEAR, MAX_YEAR),
+        ));
+    }
+}
+
+impl ToDo {
+    /// Every rule the todo breaks, empty when it is valid. Tags are checked as
+    /// typed, before `tags::normalize_tags` cleans them up
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+
+        if self.todo_info.trim().is_empty() {
+            errors.push(FieldError::new("todo_info", "Must not be empty"));
+        } else if self.todo_info.chars().count() > MAX_TODO_INFO_LEN {
+            errors.push(FieldError::new(
+                "todo_info",
+                format!("Must be at most {} characters", MAX_TODO_INFO_LEN),
+            ));
+        }
+
+        check_date(&mut errors, "created_at", Some(self.created_at));
+        check_date(&mut errors, "due_at", self.due_at);
+        if self.updated_at < self.created_at {
+            errors.push(FieldError::new(
+                "updated_at",
+                "Must not be before created_at",
+            ));
+        }
+        if self.completed_at.is_some_and(|at| at < self.created_at) {
+            errors.push(FieldError::new(
+                "completed_at",
+                "Must not be before created_at",
+            ));
+        }
+
+        if self.tags.len() > MAX_TAGS {
+            errors.push(FieldError::new(
+                "tags",
+                format!("At most {} tags", MAX_TAGS),
+            ));
+        }
+        for tag in &self.tags {
+            if normalize_tag(tag).is_none() {
+                errors.push(FieldError::new(
+                    "tags",
+                    format!("`{}` has no letters or digits", tag),
+                ));
+            } else if tag.trim().trim_start_matches('#').chars().count() > MAX_TAG_LEN {
+                errors.push(FieldError::new(
+                    "tags",
+                    format!("`{}` is longer than {} characters", tag, MAX_TAG_LEN),
+                ));
+            }
+        }
+
+        if self.checklist.len() > MAX_CHECKLIST_ITEMS {
+            errors.push(FieldError::new(
+                "checklist",
+                format!("At most {} items", MAX_CHECKLIST_ITEMS),
+            ));
+        }
+        if self
+            .checklist
+            .iter()
+            .any(|item| item.text.trim().is_empty())
+        {
+            errors.push(FieldError::new("checklist", "Items must not be empty"));
+        }
+        if self
+            .checklist
+            .iter()
+            .any(|item| item.text.chars().count() > MAX_ITEM_LEN)
+        {
+            errors.push(FieldError::new(
+                "checklist",
+                format!("Items must be at most {} characters", MAX_ITEM_LEN),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Validates the fields `patch` sets as they would be once applied, so
+    /// that a stored todo breaking a newer rule can still be changed otherwise
+    pub fn validate_patch(&self, patch: &ToDoPatch) -> Result<(), Vec<FieldError>> {
+        let mut patched = self.clone();
+        patched.apply(patch.clone());
+        if let Some(tags) = &patch.tags {
+            patched.tags = tags.clone();
+        }
+        let sets = |field: &str| match field {
+            "todo_info" => patch.todo_info.is_some(),
+            "due_at" => patch.due_at.is_some(),
+            "tags" => patch.tags.is_some(),
+            "checklist" => patch.checklist.is_some(),
+            "completed_at" => patch.completed.is_some(),
+            _ => false,
+        };
+
+        let errors: Vec<FieldError> = patched
+            .validate()
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|error| sets(&error.field))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl NewToDo {
+    /// Validates the todo this payload creates
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        ToDo {
+            tags: self.tags.clone(),
+            ..self.clone().into_todo(0)
+        }
+        .validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checklist::ChecklistItem;
+    use chrono::TimeZone;
+
+    fn todo() -> ToDo {
+        ToDo::new("Water the plants", 1)
+    }
+
+    fn fields(result: Result<(), Vec<FieldError>>) -> Vec<String> {
+        result
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    fn items(n: usize, text: &str) -> Vec<ChecklistItem> {
+        (1..=n)
+            .map(|id| ChecklistItem {
+                id,
+                text: text.to_string(),
+                done: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn todo_info_length() {
+        for (todo_info, valid) in [
+            ("é".repeat(MAX_TODO_INFO_LEN), true),
+            ("é".repeat(MAX_TODO_INFO_LEN + 1), false),
+            (" \n".to_string(), false),
+        ] {
+            let todo = ToDo {
+                todo_info,
+                ..todo()
+            };
+            assert_eq!(todo.validate().is_ok(), valid);
+        }
+        let todo = ToDo {
+            todo_info: "x".repeat(MAX_TODO_INFO_LEN + 1),
+            ..todo()
+        };
+        assert_eq!(fields(todo.validate()), vec!["todo_info"]);
+    }
+
+    #[test]
+    fn tags() {
+        let tags = |tags: Vec<String>| fields(ToDo { tags, ..todo() }.validate());
+        assert!(tags(vec!["tag".to_string(); MAX_TAGS]).is_empty());
+        assert_eq!(tags(vec!["tag".to_string(); MAX_TAGS + 1]), vec!["tags"]);
+        assert!(tags(vec![format!("#{}", "é".repeat(MAX_TAG_LEN))]).is_empty());
+        assert_eq!(tags(vec!["é".repeat(MAX_TAG_LEN + 1)]), vec!["tags"]);
+        assert_eq!(
+            tags(vec!["#".to_string(), "--".to_string()]),
+            vec!["tags", "tags"]
+        );
+    }
+
+    #[test]
+    fn due_dates() {
+        let due = |year: i32| {
+            let due_at = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+            ToDo {
+                due_at: Some(due_at),
+                ..todo()
+            }
+            .validate()
+            .is_ok()
+        };
+        assert!(!due(MIN_YEAR - 1));
+        assert!(due(MIN_YEAR));
+        assert!(due(MAX_YEAR));
+        assert!(!due(MAX_YEAR + 1));
+    }
+
+    #[test]
+    fn checklist() {
+        let checklist = |checklist: Vec<ChecklistItem>| {
+            fields(
+                ToDo {
+                    checklist,
+                    ..todo()
+                }
+                .validate(),
+            )
+        };
+        assert!(checklist(items(MAX_CHECKLIST_ITEMS, "Step")).is_empty());
+        assert_eq!(
+            checklist(items(MAX_CHECKLIST_ITEMS + 1, "Step")),
+            vec!["checklist"]
+        );
+        assert!(checklist(items(1, &"é".repeat(MAX_ITEM_LEN))).is_empty());
+        assert_eq!(
+            checklist(items(1, &"é".repeat(MAX_ITEM_LEN + 1))),
+            vec!["checklist"]
+        );
+        assert_eq!(checklist(items(1, " ")), vec!["checklist"]);
+    }
+
+    #[test]
+    fn patches_only_check_the_fields_they_set() {
+        // Stored before the length limit
+        let legacy = ToDo {
+            todo_info: "x".repeat(MAX_TODO_INFO_LEN + 1),
+            ..todo()
+        };
+        assert!(legacy.validate().is_err());
+        assert!(legacy.validate_patch(&ToDoPatch::completed(true)).is_ok());
+        let patch = ToDoPatch {
+            tags: Some(vec!["garden".to_string()]),
+            ..ToDoPatch::default()
+        };
+        assert!(legacy.validate_patch(&patch).is_ok());
+
+        let patch = ToDoPatch {
+            todo_info: Some("x".repeat(MAX_TODO_INFO_LEN + 1)),
+            tags: Some(vec!["#".to_string()]),
+            ..ToDoPatch::default()
+        };
+        assert_eq!(
+            fields(todo().validate_patch(&patch)),
+            vec!["todo_info", "tags"]
+        );
+        assert_eq!(
+            fields(legacy.validate_patch(&patch)),
+            vec!["todo_info", "tags"]
+        );
+    }
+}
@@ -1,12 +1,28 @@
-use chrono::{DateTime, Utc};
-use common::ToDo;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use common::batch::{BatchOp, MAX_BATCH_OPS};
+use common::checklist::ChecklistItemPatch;
+use common::history::{LineDiff, Revision, changes, diff_lines};
+use common::lists::{INBOX_LIST_ID, NewTaskList, TaskList};
+use common::query::{ListQuery, SortBy, SortOrder};
+use common::recurrence::Recurrence;
+use common::tags::TagCount;
+use common::validation::{FieldError, error_for};
+use common::{Priority, ToDo};
+use sample_todo_yew::markdown;
 use sample_todo_yew::todo::{
-    self, ActionType, FormState, Msg, TaskError, get_todo, manage_action_request,
+    self, ActionType, FormState, Msg, TaskError, add_item, batch, create_list, delete_item,
+    delete_list, empty_trash, get_history, get_lists, get_tags, get_todo, get_trash,
+    idempotency_key, manage_action_request, move_todo, reorder_items, restore_todo, revert_todo,
+    update_item,
 };
-use sample_todo_yew::todo::{ToDoListProps, ToDoState};
+use sample_todo_yew::todo::{
+    Conflict, ConflictProps, HistoryProps, ListControlsProps, ListSwitcherProps, NoteProps,
+    ToDoListProps, ToDoState, TrashProps, UpdateToDoProps,
+};
+use std::future::Future;
 use std::rc::Rc;
 use todo::Task;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::prelude::*;
 
 fn handle_action(
@@ -15,38 +31,477 @@ fn handle_action(
     action_type: &ActionType,
     form_state: UseStateHandle<FormState>,
 ) {
-    let task = match action_type {
-        ActionType::Delete => Task::Delete((*todo).clone()),
-        ActionType::Add => Task::Add((*todo).clone()),
-        ActionType::Update => Task::Update((*todo).clone()),
-    };
+    let task = action_type.to_task((*todo).clone());
 
     let todo_async = todo.clone();
     let reducer_async = reducer.clone();
     let action_type_async = action_type.clone();
 
-    if todo.todo_info.len() == 0 {
-        reducer.dispatch(Msg::Error(TaskError::GenericError(
-            "ToDo cannot be empty".to_string(),
-        )));
+    let invalid = match action_type {
+        ActionType::Add | ActionType::Update => todo.validate().err(),
+        ActionType::Delete | ActionType::Toggle => None,
+    };
+    if let Some(errors) = invalid {
+        let errors: Vec<String> = errors.iter().map(FieldError::to_string).collect();
+        reducer.dispatch(Msg::Error(TaskError::Invalid(errors.join(", "))));
     } else {
         reducer.dispatch(Msg::OnGoing(task.clone()));
         wasm_bindgen_futures::spawn_local(async move {
-            match manage_action_request(action_type_async, (*todo_async).clone()).await {
-                Ok(_) => {
-                    reducer_async.dispatch(Msg::Done(task.clone()));
+            match manage_action_request(action_type_async.clone(), (*todo_async).clone()).await {
+                Ok(stored) => {
+                    reload_if_recurred(&todo_async, &stored, reducer_async.clone());
+                    reducer_async.dispatch(Msg::Done(action_type_async.to_task(stored)));
+                    form_state.set(FormState::Hidden);
+                }
+                // Nothing is lost, the conflict keeps the edits for the user to retry
+                Err(TaskError::Stale(theirs)) => {
+                    let conflict = Conflict {
+                        action: action_type_async,
+                        mine: (*todo_async).clone(),
+                        theirs: *theirs,
+                    };
+                    reducer_async.dispatch(Msg::Conflict(Some(conflict.into())));
                     form_state.set(FormState::Hidden);
                 }
-                Err(_) => reducer_async.dispatch(Msg::Error(TaskError::DeleteError)),
+                Err(task_error) => reducer_async.dispatch(Msg::Error(task_error)),
             }
         });
     }
 }
 
+/// Edit refused because the todo changed elsewhere, with what differs and a
+/// choice between both versions
+#[function_component(ConflictBanner)]
+fn conflict_banner(ConflictProps { state }: &ConflictProps) -> Html {
+    let Some(conflict) = state.conflict.clone() else {
+        return html! {};
+    };
+
+    let on_keep_mine = {
+        let state = state.clone();
+        let conflict = conflict.clone();
+        Callback::from(move |_| {
+            let state = state.clone();
+            let action = conflict.action.clone();
+            // Against the version seen now, so it wins unless it changes again
+            let mine = ToDo {
+                version: conflict.theirs.version,
+                ..conflict.mine.clone()
+            };
+            wasm_bindgen_futures::spawn_local(async move {
+                match manage_action_request(action.clone(), mine.clone()).await {
+                    Ok(stored) => {
+                        state.dispatch(Msg::Conflict(None));
+                        state.dispatch(Msg::Done(action.to_task(stored)));
+                    }
+                    Err(TaskError::Stale(theirs)) => {
+                        let conflict = Conflict {
+                            action,
+                            mine,
+                            theirs: *theirs,
+                        };
+                        state.dispatch(Msg::Conflict(Some(conflict.into())));
+                    }
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+    let on_take_theirs = {
+        let state = state.clone();
+        let theirs = conflict.theirs.clone();
+        Callback::from(move |_| {
+            state.dispatch(Msg::Conflict(None));
+            state.dispatch(Msg::Done(Task::Update(theirs.clone())));
+        })
+    };
+
+    let keep_label = match conflict.action {
+        ActionType::Delete => "Delete anyway",
+        _ => "Keep mine",
+    };
+    let lines = diff_lines(&conflict.theirs.todo_info, &conflict.mine.todo_info);
+    html! {
+        <div class="fixed bottom-4 right-4 z-50 w-full max-w-md rounded border border-yellow-400 bg-yellow-50 p-4 text-sm shadow-lg">
+            <strong class="font-bold">{"This todo was changed elsewhere"}</strong>
+            <p class="text-gray-600">{"Lines and fields you changed against the saved version:"}</p>
+            <pre class="my-2 whitespace-pre-wrap">
+                {for lines.into_iter().map(|line| match line {
+                    LineDiff::Same(line) => html! {<div class="text-gray-500">{format!("  {}", line)}</div>},
+                    LineDiff::Added(line) => html! {<div class="bg-green-100 text-green-800">{format!("+ {}", line)}</div>},
+                    LineDiff::Removed(line) => html! {<div class="bg-red-100 text-red-800">{format!("- {}", line)}</div>},
+                })}
+            </pre>
+            <ul class="text-xs">
+                {for changes(&conflict.theirs, &conflict.mine).into_iter().map(|change| html! {
+                    <li>
+                        <span class="font-semibold">{change.field}</span>{": saved "}
+                        <span class="text-red-700">{change.before}</span>{", yours "}
+                        <span class="text-green-700">{change.after}</span>
+                    </li>
+                })}
+            </ul>
+            <div class="mt-2 flex justify-end gap-2">
+                <button class="rounded bg-gray-200 px-3 py-1 hover:bg-gray-300" onclick={on_take_theirs}>{"Use saved version"}</button>
+                <button class="rounded bg-yellow-500 px-3 py-1 text-white hover:bg-yellow-600" onclick={on_keep_mine}>{keep_label}</button>
+            </div>
+        </div>
+    }
+}
+
+/// Completing a recurring todo creates its next occurrence on the server,
+/// reloading shows it
+fn reload_if_recurred(before: &ToDo, saved: &ToDo, reducer: UseReducerHandle<ToDoState>) {
+    if !before.completed && saved.completed && before.recurrence.is_some() {
+        let query = ListQuery {
+            cursor: None,
+            ..reducer.query.clone()
+        };
+        load_page(reducer, query);
+    }
+}
+
+/// Repeat choices offered by the forms
+const RECURRENCE_PRESETS: [(&str, &str); 5] = [
+    ("", "Never"),
+    ("FREQ=DAILY", "Daily"),
+    ("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR", "Weekdays"),
+    ("FREQ=WEEKLY", "Weekly"),
+    ("FREQ=MONTHLY", "Monthly"),
+];
+
+/// Selector of the recurrence of a todo, a rule that is none of the presets
+/// is kept as its own choice
+fn recurrence_select(selected: Option<&Recurrence>, onchange: Callback<Event>) -> Html {
+    let selected = selected.map(Recurrence::to_string).unwrap_or_default();
+    let custom = RECURRENCE_PRESETS
+        .iter()
+        .all(|(rule, _)| *rule != selected)
+        .then(|| selected.clone());
+
+    html! {
+        <select class="border rounded px-2 py-1" {onchange}>
+            {for RECURRENCE_PRESETS.iter().map(|(rule, label)| html! {
+                <option value={*rule} selected={*rule == selected}>{*label}</option>
+            })}
+            if let Some(rule) = custom {
+                <option value={rule.clone()} selected=true>{rule}</option>
+            }
+        </select>
+    }
+}
+
+/// Tags as typed, so that `ToDo::validate` sees them before the server
+/// normalizes them
+fn split_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Message shown under an input while `field` is invalid
+fn field_error(errors: &[FieldError], field: &str) -> Html {
+    match error_for(errors, field) {
+        Some(message) => html! {
+            <p class="text-center text-xs text-red-600">{format!("{}: {}", field, message)}</p>
+        },
+        None => html! {},
+    }
+}
+
+/// Cleared inputs remove the filter instead of filtering on ""
+fn non_empty(value: String) -> Option<String> {
+    (!value.trim().is_empty()).then_some(value)
+}
+
+/// Value of a date input showing `due_at`
+fn due_input_value(due_at: Option<DateTime<Utc>>) -> String {
+    due_at
+        .map(|due_at| due_at.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+/// Due date picked in a date input, due at the start of that day (UTC)
+fn parse_due(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// Selector of the priority of a todo
+fn priority_select(selected: Priority, onchange: Callback<Event>) -> Html {
+    html! {
+        <select class="border rounded px-2 py-1" {onchange}>
+            {for Priority::ALL.into_iter().map(|priority| html! {
+                <option value={priority.as_str()} selected={priority == selected}>{priority.as_str()}</option>
+            })}
+        </select>
+    }
+}
+
+/// Loads the page of `query`, the first one when it has no cursor
+fn load_page(reducer: UseReducerHandle<ToDoState>, query: ListQuery) {
+    reducer.dispatch(Msg::OnGoing(Task::Loaded(vec![])));
+    wasm_bindgen_futures::spawn_local(async move {
+        match get_todo(&query).await {
+            Ok(page) => reducer.dispatch(Msg::Done(Task::Page(query, page))),
+            Err(task_error) => reducer.dispatch(Msg::Error(task_error)),
+        }
+    });
+}
+
+/// Reloads the lists to switch between
+fn load_lists(reducer: UseReducerHandle<ToDoState>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        match get_lists().await {
+            Ok(lists) => reducer.dispatch(Msg::Lists(lists)),
+            Err(task_error) => reducer.dispatch(Msg::Error(task_error)),
+        }
+    });
+}
+
+/// Checklist edits are saved right away, the other unsaved edits of the
+/// form are kept
+fn save_checklist(
+    edit: impl Future<Output = Result<ToDo, TaskError>> + 'static,
+    form_todo: UseStateHandle<ToDo>,
+    reducer: UseReducerHandle<ToDoState>,
+) {
+    wasm_bindgen_futures::spawn_local(async move {
+        match edit.await {
+            Ok(saved) => {
+                reload_if_recurred(&form_todo, &saved, reducer.clone());
+                form_todo.set(ToDo {
+                    checklist: saved.checklist.clone(),
+                    completed: saved.completed,
+                    completed_at: saved.completed_at,
+                    version: saved.version,
+                    ..(*form_todo).clone()
+                });
+                reducer.dispatch(Msg::Done(Task::Update(saved)));
+            }
+            Err(task_error) => reducer.dispatch(Msg::Error(task_error)),
+        }
+    });
+}
+
+/// First page of the todos of `list`, sorted the way the list asks for
+fn list_query(list: &TaskList) -> ListQuery {
+    ListQuery {
+        list: Some(list.id),
+        sort: list.sort,
+        order: list.order,
+        ..ListQuery::default()
+    }
+}
+
+#[function_component(ListSwitcher)]
+fn list_switcher(ListSwitcherProps { state }: &ListSwitcherProps) -> Html {
+    let new_name = use_state(String::new);
+    let selected = state.query.list.unwrap_or(INBOX_LIST_ID);
+
+    let on_name_input = {
+        let new_name = new_name.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            new_name.set(input.value());
+        })
+    };
+
+    let on_create = {
+        let state = state.clone();
+        let new_name = new_name.clone();
+        Callback::from(move |_| {
+            let Some(name) = non_empty((*new_name).clone()) else {
+                return;
+            };
+            let state = state.clone();
+            let new_name = new_name.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let new_list = NewTaskList {
+                    name,
+                    ..NewTaskList::default()
+                };
+                match create_list(&new_list, &idempotency_key()).await {
+                    Ok(list) => {
+                        new_name.set(String::new());
+                        load_lists(state.clone());
+                        load_page(state, list_query(&list));
+                    }
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+
+    let on_delete = {
+        let state = state.clone();
+        Callback::from(move |_| {
+            let state = state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match delete_list(selected).await {
+                    Ok(()) => {
+                        load_lists(state.clone());
+                        load_page(state, list_query(&TaskList::inbox()));
+                    }
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div class="flex justify-center items-center gap-2 py-2 text-sm">
+            {for state.lists.iter().map(|list| {
+                let onclick = {
+                    let state = state.clone();
+                    let query = list_query(list);
+                    Callback::from(move |_| load_page(state.clone(), query.clone()))
+                };
+                html! {
+                    <button style={format!("background-color: {}", list.colour)} {onclick}
+                        class={classes!("rounded-full", "px-3", "py-1", "text-white",
+                            (list.id == selected).then_some("ring-2 ring-offset-1 ring-sky-700"))}>
+                        {list.name.clone()}
+                    </button>
+                }
+            })}
+            if selected != INBOX_LIST_ID {
+                <button class="text-red-600 hover:underline" onclick={on_delete}>{"Delete list"}</button>
+            }
+            <input type="text" placeholder="New list.." value={(*new_name).clone()} oninput={on_name_input}
+                class="border rounded px-2 py-1"/>
+            <button class="bg-sky-500 hover:bg-sky-700 text-white font-bold py-1 px-3 rounded" onclick={on_create}>{"Add list"}</button>
+        </div>
+    }
+}
+
+#[function_component(ListControls)]
+fn list_controls(ListControlsProps { state }: &ListControlsProps) -> Html {
+    // Every change reloads the listing from its first page
+    let on_change = |apply: fn(&mut ListQuery, String)| {
+        let state = state.clone();
+        Callback::from(move |e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let mut query = state.query.first_page();
+            apply(&mut query, input.value());
+            load_page(state.clone(), query);
+        })
+    };
+    let on_select = |apply: fn(&mut ListQuery, String)| {
+        let state = state.clone();
+        Callback::from(move |e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            let mut query = state.query.first_page();
+            apply(&mut query, select.value());
+            load_page(state.clone(), query);
+        })
+    };
+    let tags: UseStateHandle<Vec<TagCount>> = use_state(Vec::new);
+    {
+        let tags = tags.clone();
+        use_effect_with((), move |_| {
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Ok(loaded) = get_tags().await {
+                    tags.set(loaded);
+                }
+            });
+            || ()
+        });
+    }
+    // One batch per `MAX_BATCH_OPS` todos, each deleting all or none of them
+    let on_clear_completed = {
+        let state = state.clone();
+        Callback::from(move |_| {
+            let ops: Vec<BatchOp> = state
+                .todos
+                .iter()
+                .filter(|todo| todo.completed)
+                .map(|todo| BatchOp::Delete {
+                    id: todo.id,
+                    version: Some(todo.version),
+                })
+                .collect();
+            let state = state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                for chunk in ops.chunks(MAX_BATCH_OPS) {
+                    let ops = chunk.to_vec();
+                    match batch(ops, &idempotency_key()).await {
+                        Ok(results) => state.dispatch(Msg::Done(Task::Batch(results))),
+                        Err(task_error) => {
+                            state.dispatch(Msg::Error(task_error));
+                            return;
+                        }
+                    }
+                }
+            });
+        })
+    };
+    let completed = state.todos.iter().filter(|todo| todo.completed).count();
+    let query = &state.query;
+    html! {
+        <div class="flex justify-center items-center gap-2 py-2 text-sm">
+            <input type="search" placeholder="Search notes.." value={query.text.clone().unwrap_or_default()}
+                class="border rounded px-2 py-1"
+                onchange={on_change(|query, value| query.text = non_empty(value))}/>
+            <label>{"From"}</label>
+            <input type="date" value={query.from.map(|from| from.to_string()).unwrap_or_default()} class="border rounded px-2 py-1"
+                onchange={on_change(|query, value| query.from = value.parse().ok())}/>
+            <label>{"To"}</label>
+            <input type="date" value={query.to.map(|to| to.to_string()).unwrap_or_default()} class="border rounded px-2 py-1"
+                onchange={on_change(|query, value| query.to = value.parse().ok())}/>
+            <select class="border rounded px-2 py-1" onchange={on_select(|query, value| {
+                query.sort = match value.as_str() {
+                    "updated" => SortBy::Updated,
+                    "due" => SortBy::Due,
+                    "priority" => SortBy::Priority,
+                    _ => SortBy::Created,
+                };
+            })}>
+                <option value="created" selected={matches!(query.sort, SortBy::Id | SortBy::Created)}>{"Created"}</option>
+                <option value="updated" selected={query.sort == SortBy::Updated}>{"Updated"}</option>
+                <option value="due" selected={query.sort == SortBy::Due}>{"Due"}</option>
+                <option value="priority" selected={query.sort == SortBy::Priority}>{"Priority"}</option>
+            </select>
+            <select class="border rounded px-2 py-1" onchange={on_select(|query, value| {
+                query.order = if value == "desc" { SortOrder::Desc } else { SortOrder::Asc };
+            })}>
+                <option value="asc" selected={query.order == SortOrder::Asc}>{"Ascending"}</option>
+                <option value="desc" selected={query.order == SortOrder::Desc}>{"Descending"}</option>
+            </select>
+            <select class="border rounded px-2 py-1" onchange={on_select(|query, value| {
+                query.completed = value.parse().ok();
+            })}>
+                <option value="" selected={query.completed.is_none()}>{"All"}</option>
+                <option value="false" selected={query.completed == Some(false)}>{"Open"}</option>
+                <option value="true" selected={query.completed == Some(true)}>{"Completed"}</option>
+            </select>
+            <select class="border rounded px-2 py-1" onchange={on_select(|query, value| query.tag = non_empty(value))}>
+                <option value="" selected={query.tag.is_none()}>{"All tags"}</option>
+                {for tags.iter().map(|tag| html! {
+                    <option value={tag.tag.clone()} selected={query.tag.as_deref() == Some(tag.tag.as_str())}>
+                        {format!("{} ({})", tag.tag, tag.count)}
+                    </option>
+                })}
+            </select>
+            <button class="border rounded px-2 py-1 hover:bg-gray-100 disabled:opacity-50"
+                disabled={completed == 0} onclick={on_clear_completed}>
+                {format!("Clear completed ({})", completed)}
+            </button>
+        </div>
+    }
+}
+
 #[function_component(ToDoList)]
 fn todo_list(
     ToDoListProps {
-        state, form_state, ..
+        state,
+        form_state,
+        on_click,
+        ..
     }: &ToDoListProps,
 ) -> Html {
     // let dropdown = use_state(|| false);
@@ -75,21 +530,86 @@ fn todo_list(
                         <div class="flex  justify-center items-center py-6" >
                             <h2 class="mb-4 text-4xl font-bold tracking-tight text-heading md:text-5xl lg:text-3xl">{"ToDos"}</h2>
                         </div>
+                        <ListSwitcher state={state.clone()} />
+                        <ListControls state={state.clone()} />
                         <div class="flex  justify-center items-center py-3">
                         <table class="w-4/5 bg-sky-100 text-sm text-left text-gray-500 dark:text-gray-400">
-                            <th scope="col" class="px-4 py-3">{"Date"}</th>
+                            <th scope="col" class="px-4 py-3">{"Done"}</th>
+                            <th scope="col" class="px-4 py-3">{"Created"}</th>
+                            <th scope="col" class="px-4 py-3">{"Due"}</th>
+                            <th scope="col" class="px-4 py-3">{"Priority"}</th>
+                            <th scope="col" class="px-4 py-3">{"Tags"}</th>
+                            <th scope="col" class="px-4 py-3">{"Steps"}</th>
+                            <th scope="col" class="px-4 py-3">{"List"}</th>
                             <th scope="col" class="px-4 py-3">{"Note"}</th>
+                            <th scope="col" class="px-4 py-3">{"History"}</th>
+                            <th scope="col" class="px-4 py-3">{"Edit"}</th>
                             <th scope="col" class="px-4 py-3">{"Delete"}</th>
                                 <tbody>
                                     {for state.todos.iter().map(|todo| {
                                         // This shot has to change
                                         let reducer = state.clone();
                                         let todo_rf_on_click = Rc::new((*todo).clone());
+                                        let on_edit = {
+                                            let todo = (*todo).clone();
+                                            let on_click = on_click.clone();
+                                            let form_state = form_state.clone();
+                                            Callback::from(move |_| {
+                                                on_click.emit(todo.clone());
+                                                form_state.set(FormState::Visible(ActionType::Update));
+                                            })
+                                        };
+                                        let on_move = {
+                                            let state = state.clone();
+                                            let todo = (*todo).clone();
+                                            Callback::from(move |e: Event| {
+                                                let select: HtmlSelectElement = e.target_unchecked_into();
+                                                let Ok(list_id) = select.value().parse() else {
+                                                    return;
+                                                };
+                                                let state = state.clone();
+                                                let todo = todo.clone();
+                                                wasm_bindgen_futures::spawn_local(async move {
+                                                    match move_todo(&todo, list_id).await {
+                                                        // Gone from the list shown
+                                                        Ok(moved) if state.query.list.is_some_and(|list| list != moved.list_id) => {
+                                                            state.dispatch(Msg::Done(Task::Delete(moved)))
+                                                        }
+                                                        Ok(moved) => state.dispatch(Msg::Done(Task::Update(moved))),
+                                                        Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                                                    }
+                                                });
+                                            })
+                                        };
                                         html!{
 
-                                        <tr class="border-b dark:border-gray-700">
-                                        <td class="px-4 py-3">{todo.todo_date.clone()}</td>
-                                        <td class="px-4 py-3">{todo.todo_info.clone()}</td>
+                                        <tr class={classes!("border-b", "dark:border-gray-700", todo.completed.then_some("line-through"))}>
+                                        <td class="px-4 py-3">{get_button(ActionType::Toggle, todo_rf_on_click.clone(), reducer.clone(), form_state.clone())}</td>
+                                        <td class="px-4 py-3">{todo.created_at.format("%Y-%m-%d %H:%M").to_string()}</td>
+                                        <td class="px-4 py-3">
+                                            {due_input_value(todo.due_at)}
+                                            if let Some(recurrence) = &todo.recurrence {
+                                                <span class="ml-1" title={recurrence.to_string()}>{"↻"}</span>
+                                            }
+                                        </td>
+                                        <td class="px-4 py-3">{todo.priority.as_str()}</td>
+                                        <td class="px-4 py-3">
+                                            {for todo.tags.iter().map(|tag| html! {
+                                                <span class="mr-1 rounded bg-sky-200 px-2 py-0.5 text-xs text-sky-800">{tag}</span>
+                                            })}
+                                        </td>
+                                        <td class="px-4 py-3">
+                                            {todo.progress().map(|(done, total)| format!("{}/{}", done, total)).unwrap_or_default()}
+                                        </td>
+                                        <td class="px-4 py-3">
+                                            <select class="border rounded px-2 py-1" onchange={on_move}>
+                                                {for state.lists.iter().map(|list| html! {
+                                                    <option value={list.id.to_string()} selected={list.id == todo.list_id}>{list.name.clone()}</option>
+                                                })}
+                                            </select>
+                                        </td>
+                                        <td class="px-4 py-3"><Note note={todo.todo_info.clone()} /></td>
+                                        <td class="px-4 py-3"><History todo_id={todo.id} state={state.clone()} /></td>
 
                                         // <td class="px-4 py-3">{get_button(ActionType::Delete, todo_rf_on_click.clone(), reducer.clone())}</td>
                                         // <td class="px-4 py-3">{get_button(ActionType::Update, todo_rf_on_click.clone(), reducer.clone())}</td>
@@ -102,6 +622,11 @@ fn todo_list(
                                         //     </button>
 
                                         // </td>
+                                        <td>
+                                            <div class="flex items-center justify-center">
+                                                <button class="bg-green-600 hover:bg-green-800 text-white font-bold py-2 px-4 rounded" onclick={on_edit}>{"Edit"}</button>
+                                            </div>
+                                        </td>
                                         <td>
                                             <div class="flex items-center justify-center">
                                                 {get_button(ActionType::Delete, todo_rf_on_click.clone(), reducer.clone(),form_state.clone())}
@@ -112,6 +637,18 @@ fn todo_list(
                                 </tbody>
                         </table>
                     </div>
+                    if let Some(cursor) = state.next_cursor.clone() {
+                        <div class="flex justify-center py-3">
+                            <button class="bg-sky-500 hover:bg-sky-700 text-white font-bold py-2 px-4 rounded" onclick={
+                                let state = state.clone();
+                                move |_| load_page(state.clone(), ListQuery {
+                                    cursor: Some(cursor.clone()),
+                                    ..state.query.clone()
+                                })
+                            }>{"Load more"}</button>
+                        </div>
+                    }
+                    <Trash state={state.clone()} />
                     </>
                 }
             }
@@ -172,6 +709,13 @@ fn get_button(
                 </div>
             }
         }
+        ActionType::Toggle => {
+            html! {
+                <input type="checkbox" class="w-4 h-4" checked={todo.completed} onclick={move |_| {
+                    handle_action(todo.clone(), reducer.clone(), &action_type, form_state.clone());
+                }}/>
+            }
+        }
         ActionType::Add => {
             html! {
                 <div>
@@ -185,17 +729,298 @@ fn get_button(
     }
 }
 
+/// Deleted todos, they can be restored until the server purges them
+#[function_component(Trash)]
+fn trash(TrashProps { state }: &TrashProps) -> Html {
+    let open = use_state(|| false);
+    let trashed: UseStateHandle<Vec<ToDo>> = use_state(Vec::new);
+
+    let on_toggle = {
+        let open = open.clone();
+        let trashed = trashed.clone();
+        let state = state.clone();
+        Callback::from(move |_| {
+            if *open {
+                open.set(false);
+                return;
+            }
+            open.set(true);
+            let trashed = trashed.clone();
+            let state = state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match get_trash().await {
+                    Ok(todos) => trashed.set(todos),
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+
+    let on_empty = {
+        let trashed = trashed.clone();
+        let state = state.clone();
+        Callback::from(move |_| {
+            let trashed = trashed.clone();
+            let state = state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match empty_trash().await {
+                    Ok(()) => trashed.set(vec![]),
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+
+    let rows = trashed.iter().map(|todo| {
+        let on_restore = {
+            let trashed = trashed.clone();
+            let state = state.clone();
+            let todo = todo.clone();
+            Callback::from(move |_| {
+                let trashed = trashed.clone();
+                let state = state.clone();
+                let todo = todo.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let id = todo.id;
+                    match restore_todo(&todo).await {
+                        Ok(_) => {
+                            trashed.set(trashed.iter().filter(|todo| todo.id != id).cloned().collect());
+                            let query = ListQuery {
+                                cursor: None,
+                                ..state.query.clone()
+                            };
+                            load_page(state, query);
+                        }
+                        Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                    }
+                });
+            })
+        };
+
+        html! {
+            <tr class="border-b">
+                <td class="px-4 py-2">{markdown::summary(&todo.todo_info)}</td>
+                <td class="px-4 py-2">
+                    {todo.deleted_at.map(|at| at.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default()}
+                </td>
+                <td class="px-4 py-2">
+                    <button class="text-blue-600 hover:underline" onclick={on_restore}>{"Restore"}</button>
+                </td>
+            </tr>
+        }
+    });
+
+    html! {
+        <div class="flex flex-col items-center py-3 text-sm">
+            <button class="text-gray-600 hover:underline" onclick={on_toggle}>
+                {if *open { "Hide trash" } else { "Show trash" }}
+            </button>
+            if *open {
+                <div class="w-4/5 py-2">
+                    if trashed.is_empty() {
+                        <p class="text-center text-gray-500">{"The trash is empty"}</p>
+                    } else {
+                        <table class="w-full bg-gray-100 text-left text-gray-500">
+                            <tbody>{for rows}</tbody>
+                        </table>
+                        <div class="flex justify-end py-2">
+                            <button class="bg-red-500 hover:bg-red-700 text-white px-3 py-1 rounded" onclick={on_empty}>
+                                {"Empty trash"}
+                            </button>
+                        </div>
+                    }
+                </div>
+            }
+        </div>
+    }
+}
+
+/// What changed from `before` to `revision`, the whole text for the oldest one
+fn revision_diff(before: Option<&Revision>, revision: &Revision) -> Html {
+    let Some(before) = before else {
+        return html! {
+            <pre class="whitespace-pre-wrap text-gray-700">{revision.todo.todo_info.clone()}</pre>
+        };
+    };
+
+    let lines = diff_lines(&before.todo.todo_info, &revision.todo.todo_info);
+    let text_changed = lines.iter().any(|line| !matches!(line, LineDiff::Same(_)));
+    html! {
+        <>
+            if text_changed {
+                <pre class="whitespace-pre-wrap">
+                    {for lines.into_iter().map(|line| match line {
+                        LineDiff::Same(line) => html! {<div class="text-gray-500">{format!("  {}", line)}</div>},
+                        LineDiff::Added(line) => html! {<div class="bg-green-100 text-green-800">{format!("+ {}", line)}</div>},
+                        LineDiff::Removed(line) => html! {<div class="bg-red-100 text-red-800">{format!("- {}", line)}</div>},
+                    })}
+                </pre>
+            }
+            <ul class="text-xs">
+                {for changes(&before.todo, &revision.todo).into_iter().map(|change| html! {
+                    <li>
+                        <span class="font-semibold">{change.field}</span>{": "}
+                        <span class="text-red-700 line-through">{change.before}</span>{" → "}
+                        <span class="text-green-700">{change.after}</span>
+                    </li>
+                })}
+            </ul>
+        </>
+    }
+}
+
+/// Drawer listing the revisions of a todo, the newest first, each with what
+/// it changed and a way back to it
+#[function_component(History)]
+fn history(HistoryProps { todo_id, state }: &HistoryProps) -> Html {
+    let open = use_state(|| false);
+    let revisions: UseStateHandle<Vec<Revision>> = use_state(Vec::new);
+
+    let load = {
+        let revisions = revisions.clone();
+        let state = state.clone();
+        let todo_id = *todo_id;
+        Callback::from(move |_: ()| {
+            let revisions = revisions.clone();
+            let state = state.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match get_history(todo_id).await {
+                    Ok(history) => revisions.set(history),
+                    Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                }
+            });
+        })
+    };
+
+    let on_open = {
+        let open = open.clone();
+        let load = load.clone();
+        Callback::from(move |_| {
+            open.set(true);
+            load.emit(());
+        })
+    };
+    let on_close = {
+        let open = open.clone();
+        Callback::from(move |_| open.set(false))
+    };
+
+    let latest = revisions.last().map(|revision| revision.rev);
+    // Version of the todo as of the revisions shown
+    let version = revisions
+        .last()
+        .map(|revision| revision.todo.version)
+        .unwrap_or_default();
+    let entries = revisions.iter().enumerate().rev().map(|(index, revision)| {
+        let before = index.checked_sub(1).and_then(|index| revisions.get(index));
+        let on_revert = {
+            let state = state.clone();
+            let load = load.clone();
+            let todo_id = *todo_id;
+            let rev = revision.rev;
+            Callback::from(move |_| {
+                let state = state.clone();
+                let load = load.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    match revert_todo(todo_id, rev, version).await {
+                        // Gone from the list shown
+                        Ok(todo) if state.query.list.is_some_and(|list| list != todo.list_id) => {
+                            state.dispatch(Msg::Done(Task::Delete(todo)))
+                        }
+                        Ok(todo) => {
+                            state.dispatch(Msg::Done(Task::Update(todo)));
+                            load.emit(());
+                        }
+                        Err(task_error) => state.dispatch(Msg::Error(task_error)),
+                    }
+                });
+            })
+        };
+
+        html! {
+            <li class="border-b py-2">
+                <div class="flex items-center justify-between">
+                    <span class="font-semibold">
+                        {format!("#{} {}", revision.rev, revision.change.as_str())}
+                    </span>
+                    <span class="text-xs text-gray-500">{revision.at.format("%Y-%m-%d %H:%M:%S").to_string()}</span>
+                </div>
+                {revision_diff(before, revision)}
+                if Some(revision.rev) != latest {
+                    <button class="mt-1 text-xs text-blue-600 hover:underline" onclick={on_revert}>
+                        {"Revert to this version"}
+                    </button>
+                }
+            </li>
+        }
+    });
+
+    html! {
+        <>
+            <button class="text-gray-600 hover:underline" onclick={on_open}>{"History"}</button>
+            if *open {
+                <div class="fixed inset-y-0 right-0 z-50 w-96 overflow-y-auto bg-white p-4 text-sm shadow-xl">
+                    <div class="flex items-center justify-between pb-2">
+                        <h2 class="text-lg font-bold">{"History"}</h2>
+                        <button class="text-gray-600 hover:underline" onclick={on_close}>{"Close"}</button>
+                    </div>
+                    <ul>{for entries}</ul>
+                </div>
+            }
+        </>
+    }
+}
+
+/// First line of a note, expanding to the whole rendered note
+#[function_component(Note)]
+fn note(NoteProps { note }: &NoteProps) -> Html {
+    let expanded = use_state(|| false);
+
+    let on_toggle = {
+        let expanded = expanded.clone();
+        Callback::from(move |_| expanded.set(!*expanded))
+    };
+
+    if !markdown::has_more(note) {
+        return html! { <div class="markdown">{markdown::render(note)}</div> };
+    }
+
+    html! {
+        <div class="flex items-start gap-1">
+            <button class="text-gray-500" onclick={on_toggle}
+                aria-label={if *expanded { "Collapse note" } else { "Expand note" }}>
+                {if *expanded { "▾" } else { "▸" }}
+            </button>
+            if *expanded {
+                <div class="markdown">{markdown::render(note)}</div>
+            } else {
+                <span>{markdown::summary(note)}</span>
+            }
+        </div>
+    }
+}
+
 #[function_component(AddToDoNote)]
 fn add_todo(
     ToDoListProps {
         state, form_state, ..
     }: &ToDoListProps,
 ) -> Html {
-    let new_todo = use_state(|| ToDo {
-        todo_info: "".to_string(),
-        todo_date: "".to_string(),
-        id: state.todos.len(),
-    });
+    // The id is a placeholder, the server assigns the real one on store
+    let new_todo = use_state(|| ToDo::new("", 0));
+    // No errors on the blank form until something is typed
+    let touched = use_state(|| false);
+    let errors = if *touched {
+        new_todo.validate().err().unwrap_or_default()
+    } else {
+        vec![]
+    };
+    let preview = use_state(|| false);
+
+    let on_preview_toggle = {
+        let preview = preview.clone();
+        Callback::from(move |_| preview.set(!*preview))
+    };
 
     let onclick = {
         let form_state = form_state.clone();
@@ -212,12 +1037,62 @@ fn add_todo(
     // Handle input changes
     let on_info_change = {
         let new_todo = new_todo.clone();
+        let touched = touched.clone();
         Callback::from(move |e: InputEvent| {
-            let date: DateTime<Utc> = Utc::now();
             let mut updated = (*new_todo).clone();
             let input: HtmlInputElement = e.target_unchecked_into();
             updated.todo_info = input.value();
-            updated.todo_date = date.format("%Y-%m-%d %H:%M:%S").to_string();
+            new_todo.set(updated);
+            touched.set(true);
+        })
+    };
+
+    let on_due_change = {
+        let new_todo = new_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*new_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.due_at = parse_due(&input.value());
+            new_todo.set(updated);
+        })
+    };
+
+    let on_priority_change = {
+        let new_todo = new_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*new_todo).clone();
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            updated.priority = Priority::parse(&select.value()).unwrap_or_default();
+            new_todo.set(updated);
+        })
+    };
+
+    let on_tags_change = {
+        let new_todo = new_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*new_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.tags = split_tags(&input.value());
+            new_todo.set(updated);
+        })
+    };
+
+    let on_recurrence_change = {
+        let new_todo = new_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*new_todo).clone();
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            updated.recurrence = select.value().parse().ok();
+            new_todo.set(updated);
+        })
+    };
+
+    let on_auto_complete_change = {
+        let new_todo = new_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*new_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.auto_complete = input.checked();
             new_todo.set(updated);
         })
     };
@@ -264,18 +1139,56 @@ fn add_todo(
                 </div>
 
                 <div class="flex justify-center">
-                    <textarea value={new_todo.todo_info.clone()} oninput={on_info_change}
-                        rows="10"
-                        class="w-2/3 h-60 bg-neutral-secondary-medium border border-default-medium text-heading text-sm rounded-base focus:ring-brand focus:border-brand p-3.5 shadow-xs placeholder:text-body resize-y" placeholder="Add your ToDo.."/>
+                    <div class="flex w-2/3 justify-end pb-1 text-sm">
+                        <button class="text-blue-600 hover:underline" onclick={on_preview_toggle}>
+                            {if *preview { "Write" } else { "Preview" }}
+                        </button>
+                    </div>
+                </div>
+
+                <div class="flex justify-center">
+                    if *preview {
+                        <div class="markdown w-2/3 h-60 overflow-y-auto bg-white border border-default-medium text-heading text-sm rounded-base p-3.5 shadow-xs">
+                            {markdown::render(&new_todo.todo_info)}
+                        </div>
+                    } else {
+                        <textarea value={new_todo.todo_info.clone()} oninput={on_info_change}
+                            rows="10"
+                            class="w-2/3 h-60 bg-neutral-secondary-medium border border-default-medium text-heading text-sm rounded-base focus:ring-brand focus:border-brand p-3.5 shadow-xs placeholder:text-body resize-y" placeholder="Add your ToDo.. (Markdown)"/>
+                    }
                 </div>
+                {field_error(&errors, "todo_info")}
+
+                <div class="flex justify-center items-center gap-2 py-2 text-sm">
+                    <label>{"Due"}</label>
+                    <input type="date" value={due_input_value(new_todo.due_at)} onchange={on_due_change}
+                        class="border rounded px-2 py-1"/>
+                    <label>{"Priority"}</label>
+                    {priority_select(new_todo.priority, on_priority_change)}
+                    <label>{"Tags"}</label>
+                    <input type="text" placeholder="work, home" value={new_todo.tags.join(", ")} onchange={on_tags_change}
+                        class="border rounded px-2 py-1"/>
+                    <label>{"Repeat"}</label>
+                    {recurrence_select(new_todo.recurrence.as_ref(), on_recurrence_change)}
+                    <label>
+                        <input type="checkbox" checked={new_todo.auto_complete} onchange={on_auto_complete_change}/>
+                        {" Complete when every step is done"}
+                    </label>
+                </div>
+                {field_error(&errors, "due_at")}
+                {field_error(&errors, "tags")}
 
                 <div class="flex justify-center py-2">
-                    {get_button(ActionType::Add, Rc::new((*new_todo).clone()), state.clone(), form_state.clone())}
+                    {get_button(ActionType::Add, Rc::new(ToDo {
+                        list_id: state.query.list.unwrap_or(INBOX_LIST_ID),
+                        ..(*new_todo).clone()
+                    }), state.clone(), form_state.clone())}
                 </div>
                 </>
 
             };
         }
+        FormState::Visible(ActionType::Update) => html! {<></>},
         _ => {
             html! {
             <div role="alert" class="relative flex w-full items-start rounded-md border border-red-500 bg-red-500 p-2 text-red-50">
@@ -289,46 +1202,271 @@ fn add_todo(
 }
 
 #[function_component(UpdateToDo)]
-fn update_todo(todo: &ToDo) -> Html {
-    html! {
-        <div>
-            <label>{"Note"}</label>
-            <input type={"text"} minlength={"4"} value={todo.todo_info.clone()}/>
-            <div>
-                // Missing Button
+fn update_todo(
+    UpdateToDoProps {
+        state,
+        form_state,
+        todo,
+    }: &UpdateToDoProps,
+) -> Html {
+    let updated_todo = use_state(|| todo.clone());
+    let errors = updated_todo.validate().err().unwrap_or_default();
+
+    let on_back = {
+        let form_state = form_state.clone();
+        Callback::from(move |_| form_state.set(FormState::Hidden))
+    };
+
+    let on_info_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: InputEvent| {
+            let mut updated = (*updated_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.todo_info = input.value();
+            updated_todo.set(updated);
+        })
+    };
+
+    let on_due_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*updated_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.due_at = parse_due(&input.value());
+            updated_todo.set(updated);
+        })
+    };
+
+    let on_priority_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*updated_todo).clone();
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            updated.priority = Priority::parse(&select.value()).unwrap_or_default();
+            updated_todo.set(updated);
+        })
+    };
+
+    let on_tags_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*updated_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.tags = split_tags(&input.value());
+            updated_todo.set(updated);
+        })
+    };
+
+    let on_recurrence_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*updated_todo).clone();
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            updated.recurrence = select.value().parse().ok();
+            updated_todo.set(updated);
+        })
+    };
+
+    let on_auto_complete_change = {
+        let updated_todo = updated_todo.clone();
+        Callback::from(move |e: Event| {
+            let mut updated = (*updated_todo).clone();
+            let input: HtmlInputElement = e.target_unchecked_into();
+            updated.auto_complete = input.checked();
+            updated_todo.set(updated);
+        })
+    };
+
+    let new_item = use_state(String::new);
+
+    let on_new_item_input = {
+        let new_item = new_item.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            new_item.set(input.value());
+        })
+    };
+
+    let on_add_item = {
+        let new_item = new_item.clone();
+        let updated_todo = updated_todo.clone();
+        let state = state.clone();
+        Callback::from(move |_| {
+            let text = new_item.trim().to_string();
+            if text.is_empty() {
+                return;
+            }
+            new_item.set(String::new());
+            let (todo_id, version) = (updated_todo.id, updated_todo.version);
+            let key = idempotency_key();
+            save_checklist(
+                async move { add_item(todo_id, version, text, &key).await },
+                updated_todo.clone(),
+                state.clone(),
+            );
+        })
+    };
+
+    let todo_id = updated_todo.id;
+    let version = updated_todo.version;
+    let ids: Vec<usize> = updated_todo.checklist.iter().map(|item| item.id).collect();
+    let steps = updated_todo.checklist.iter().enumerate().map(|(index, item)| {
+        let on_toggle = {
+            let updated_todo = updated_todo.clone();
+            let state = state.clone();
+            let patch = ChecklistItemPatch {
+                done: Some(!item.done),
+                ..ChecklistItemPatch::default()
+            };
+            let item_id = item.id;
+            Callback::from(move |_| {
+                save_checklist(
+                    update_item(todo_id, version, item_id, patch.clone()),
+                    updated_todo.clone(),
+                    state.clone(),
+                )
+            })
+        };
+
+        // Swaps the item with its neighbour `other`
+        let on_swap = |other: usize| {
+            let updated_todo = updated_todo.clone();
+            let state = state.clone();
+            let mut order = ids.clone();
+            order.swap(index, other);
+            Callback::from(move |_| {
+                save_checklist(
+                    reorder_items(todo_id, version, order.clone()),
+                    updated_todo.clone(),
+                    state.clone(),
+                )
+            })
+        };
+
+        let on_delete = {
+            let updated_todo = updated_todo.clone();
+            let state = state.clone();
+            let item_id = item.id;
+            Callback::from(move |_| {
+                save_checklist(
+                    delete_item(todo_id, version, item_id),
+                    updated_todo.clone(),
+                    state.clone(),
+                )
+            })
+        };
+
+        html! {
+            <li class="flex items-center gap-2 py-1">
+                <input type="checkbox" checked={item.done} onclick={on_toggle}/>
+                <span class={classes!("grow", item.done.then_some("line-through"))}>{item.text.clone()}</span>
+                <button class="px-2 disabled:opacity-30" disabled={index == 0}
+                    onclick={on_swap(index.saturating_sub(1))}>{"↑"}</button>
+                <button class="px-2 disabled:opacity-30" disabled={index + 1 == ids.len()}
+                    onclick={on_swap((index + 1).min(ids.len() - 1))}>{"↓"}</button>
+                <button class="px-2 text-red-600" onclick={on_delete}>{"✕"}</button>
+            </li>
+        }
+    });
+
+    match **form_state {
+        FormState::Visible(ActionType::Update) => html! {
+            <>
+            <div class="py-7">
+                <button onclick={on_back}
+                    class="fixed top-4 left-4 z-50
+                           inline-flex items-center justify-center
+                           p-2 rounded-full
+                           bg-white shadow
+                           text-gray-700 hover:bg-gray-100
+                           transition-colors"
+                    aria-label="Go back"
+                >
+                    <svg
+                        xmlns="http://www.w3.org/2000/svg"
+                        fill="none"
+                        viewBox="0 0 24 24"
+                        stroke-width="1.5"
+                        stroke="currentColor"
+                        class="w-5 h-5"
+                    >
+                        <path
+                            stroke-linecap="round"
+                            stroke-linejoin="round"
+                            d="M10.5 19.5L3 12m0 0l7.5-7.5M3 12h18"
+                        />
+                    </svg>
+                </button>
             </div>
-        </div>
+            <div class="flex py-6 justify-center" >
+                <h2 class="mb-4 text-4xl font-bold tracking-tight text-heading md:text-5xl lg:text-3xl">{"Update Note"}</h2>
+            </div>
+
+            <div class="flex justify-center">
+                <textarea value={updated_todo.todo_info.clone()} oninput={on_info_change}
+                    rows="10"
+                    class="w-2/3 h-60 bg-neutral-secondary-medium border border-default-medium text-heading text-sm rounded-base focus:ring-brand focus:border-brand p-3.5 shadow-xs placeholder:text-body resize-y"/>
+            </div>
+            {field_error(&errors, "todo_info")}
+
+            <div class="flex justify-center items-center gap-2 py-2 text-sm">
+                <label>{"Due"}</label>
+                <input type="date" value={due_input_value(updated_todo.due_at)} onchange={on_due_change}
+                    class="border rounded px-2 py-1"/>
+                <label>{"Priority"}</label>
+                {priority_select(updated_todo.priority, on_priority_change)}
+                <label>{"Tags"}</label>
+                <input type="text" placeholder="work, home" value={updated_todo.tags.join(", ")} onchange={on_tags_change}
+                    class="border rounded px-2 py-1"/>
+                <label>{"Repeat"}</label>
+                {recurrence_select(updated_todo.recurrence.as_ref(), on_recurrence_change)}
+                <label>
+                    <input type="checkbox" checked={updated_todo.auto_complete} onchange={on_auto_complete_change}/>
+                    {" Complete when every step is done"}
+                </label>
+            </div>
+            {field_error(&errors, "due_at")}
+            {field_error(&errors, "tags")}
+
+            <div class="flex justify-center py-2 text-sm">
+                <div class="w-2/3">
+                    <h3 class="font-bold">{"Steps"}</h3>
+                    <ul>{for steps}</ul>
+                    {field_error(&errors, "checklist")}
+                    <div class="flex gap-2 py-1">
+                        <input type="text" placeholder="Add a step" value={(*new_item).clone()} oninput={on_new_item_input}
+                            class="grow border rounded px-2 py-1"/>
+                        <button class="bg-blue-500 hover:bg-blue-700 text-white px-3 rounded" onclick={on_add_item}>{"Add"}</button>
+                    </div>
+                </div>
+            </div>
+
+            <div class="flex justify-center py-2">
+                {get_button(ActionType::Update, Rc::new((*updated_todo).clone()), state.clone(), form_state.clone())}
+            </div>
+            </>
+        },
+        _ => html! {<></>},
     }
 }
 
 #[function_component(App)]
 fn app() -> Html {
-    let reducer = use_reducer(|| ToDoState {
-        todos: Rc::new(vec![]),
-        loading: true,
-        error: None,
-    });
+    let reducer = use_reducer(ToDoState::new);
 
     let form_state = use_state(|| FormState::new());
 
-    let selected_todo = use_state(|| None);
+    let selected_todo: UseStateHandle<Option<ToDo>> = use_state(|| None);
     let on_todo_select = {
-        let selected_video = selected_todo.clone();
-        Callback::from(move |todo: ToDo| selected_video.set(Some(todo)))
+        let selected_todo = selected_todo.clone();
+        Callback::from(move |todo: ToDo| selected_todo.set(Some(todo)))
     };
 
     {
         let reducer = reducer.clone();
         use_effect_with((), move |_| {
-            reducer.dispatch(Msg::OnGoing(Task::Loaded(vec![])));
-            wasm_bindgen_futures::spawn_local(async move {
-                match get_todo().await {
-                    Ok(todos_list_props) => {
-                        reducer.dispatch(Msg::Done(Task::Loaded(todos_list_props)))
-                    }
-                    Err(_) => reducer.dispatch(Msg::Error(TaskError::LoadError)),
-                }
-            });
+            load_lists(reducer.clone());
+            load_page(reducer, list_query(&TaskList::inbox()));
             || ()
         });
     }
@@ -350,15 +1488,16 @@ fn app() -> Html {
                         html!{}
                     }
             }
+            <ConflictBanner state={reducer.clone()} />
             <div class="">
                 <ToDoList state={reducer.clone()} form_state={form_state.clone()} on_click={on_todo_select.clone()}/>
             </div>
 
-            // <div>
-            //     if let Some(todo) = &*selected_todo{
-            //         // <UpdateToDo todo_info={todo.todo_info} />
-            //     }
-            // </div>
+            <div>
+                if let Some(todo) = &*selected_todo {
+                    <UpdateToDo key={todo.id} state={reducer.clone()} form_state={form_state.clone()} todo={todo.clone()} />
+                }
+            </div>
             <div>
 
                <AddToDoNote state={reducer.clone()} form_state={form_state.clone()} on_click={on_todo_select.clone()}/>
@@ -0,0 +1,35 @@
+use pulldown_cmark::{Event, Options, Parser, html};
+use yew::{AttrValue, Html};
+
+fn options() -> Options {
+    Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES
+}
+
+/// Renders the Markdown of a note. The HTML is sanitized, so raw HTML in the
+/// note cannot inject scripts, styles or event handlers
+pub fn render(markdown: &str) -> Html {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options()));
+
+    Html::from_html_unchecked(AttrValue::from(ammonia::clean(&unsafe_html)))
+}
+
+/// First non-blank line of a note as plain text, without its Markdown markup
+pub fn summary(markdown: &str) -> String {
+    let line = markdown
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+
+    Parser::new_ext(line, options())
+        .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) => Some(text.into_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether the note has more to show than its summary
+pub fn has_more(markdown: &str) -> bool {
+    markdown.trim().lines().nth(1).is_some()
+}
@@ -1,5 +1,13 @@
-use common::ToDo;
-use gloo_net::http::Request;
+use common::batch::{Batch, BatchOp, BatchResult};
+use common::checklist::{ChecklistItemPatch, ChecklistOrder, NewChecklistItem};
+use common::history::Revision;
+use common::lists::{MoveToDo, NewTaskList, TaskList};
+use common::query::{ListQuery, Page};
+use common::search::{SearchHit, SearchQuery};
+use common::tags::TagCount;
+use common::{ApiError, NewToDo, ToDo, ToDoPatch};
+use gloo_net::http::{Request, Response};
+use gloo_timers::future::TimeoutFuture;
 use log::info;
 use serde_json;
 use yew::prelude::*;
@@ -7,14 +15,84 @@ use yew::prelude::*;
 /// Web Assembly is a sandboxed Enviorment so is not possible to read from  local storage
 /// so we use a Redis istance to store our todo stuff
 pub const SIMPLE_SERVER: &str = "http://127.0.0.1:3000";
-pub const UNABLE_TO_PARSE_FROM_JSON: &'static str = "Unable to parse from Json";
-pub const FAILED_TO_RETRIEVE_TODO: &'static str = "Unalble to retireve data";
-pub const FAILED_TO_STORE_TODO: &'static str = "Unalble to store data";
-pub const FAILED_TO_DELETE_TODO: &'static str = "Unalble to delete data";
+pub const UNABLE_TO_PARSE_FROM_JSON: &str = "Unable to parse from Json";
+pub const FAILED_TO_RETRIEVE_TODO: &str = "Unalble to retireve data";
+pub const FAILED_TO_STORE_TODO: &str = "Unalble to store data";
+pub const FAILED_TO_DELETE_TODO: &str = "Unalble to delete data";
+pub const FAILED_TO_UPDATE_TODO: &str = "Unalble to update data";
 
 pub const SIMPLE_SERVER_GET_TODO: &str = "/get_todo";
-pub const SIMPLE_SERVER_DELETE_TODO: &'static str = "/delete_todo";
-pub const SIMPLE_SERVER_STORE_TODO: &'static str = "/store_todo";
+pub const SIMPLE_SERVER_DELETE_TODO: &str = "/delete_todo";
+pub const SIMPLE_SERVER_STORE_TODO: &str = "/store_todo";
+pub const SIMPLE_SERVER_TODOS: &str = "/todos";
+pub const SIMPLE_SERVER_SEARCH: &str = "/search";
+pub const SIMPLE_SERVER_TAGS: &str = "/tags";
+pub const SIMPLE_SERVER_LISTS: &str = "/lists";
+pub const SIMPLE_SERVER_TRASH: &str = "/trash";
+/// Times a request carrying an `Idempotency-Key` is sent before giving up,
+/// the server answers repeats with its first response
+const IDEMPOTENT_ATTEMPTS: usize = 5;
+/// Wait before sending again after a network failure
+const RETRY_DELAY_MS: u32 = 500;
+
+/// `If-Match` value making the server refuse to change a todo that has moved
+/// past `version`
+fn if_match(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Value for the `Idempotency-Key` header, make one per user action and send
+/// every retry of its request with it
+pub fn idempotency_key() -> String {
+    let mut bytes = [0u8; 16];
+    let random = web_sys::window()
+        .and_then(|window| window.crypto().ok())
+        .is_some_and(|crypto| crypto.get_random_values_with_u8_array(&mut bytes).is_ok());
+    if !random {
+        let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        bytes[..8].copy_from_slice(&nanos.to_le_bytes());
+    }
+
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Sends `body` as JSON in the request `build` makes, with `key`. It is sent
+/// again while the network fails, or while the server is still handling an
+/// earlier try and answers 409 with `Retry-After`
+async fn send_idempotent(
+    build: impl Fn() -> Request,
+    body: &str,
+    key: &str,
+) -> Result<Response, gloo_net::Error> {
+    let mut attempt = 1;
+    loop {
+        let sent = build()
+            .header("Content-Type", "application/json")
+            .header("Idempotency-Key", key)
+            .body(body)
+            .send()
+            .await;
+        let delay_ms = match &sent {
+            Err(e) => {
+                info!("Request with Idempotency-Key {} failed: {}", key, e);
+                Some(RETRY_DELAY_MS)
+            }
+            Ok(resp) if resp.status() == 409 => resp
+                .headers()
+                .get("Retry-After")
+                .and_then(|secs| secs.parse::<u32>().ok())
+                .map(|secs| secs * 1000),
+            Ok(_) => None,
+        };
+        match delay_ms {
+            Some(delay_ms) if attempt < IDEMPOTENT_ATTEMPTS => {
+                TimeoutFuture::new(delay_ms).await;
+                attempt += 1;
+            }
+            _ => return sent,
+        }
+    }
+}
 
 #[derive(PartialEq, Clone)]
 pub enum FormState {
@@ -38,6 +116,18 @@ pub enum ActionType {
     Delete,
     Add,
     Update,
+    /// Flips the completion of a todo
+    Toggle,
+}
+
+impl ActionType {
+    pub fn to_task(&self, todo: ToDo) -> Task {
+        match self {
+            Self::Delete => Task::Delete(todo),
+            Self::Add => Task::Add(todo),
+            Self::Update | Self::Toggle => Task::Update(todo),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -46,6 +136,11 @@ pub enum Task {
     Add(ToDo),
     Update(ToDo),
     Loaded(Vec<ToDo>),
+    /// Page of the listing for this query, a first page replaces the todos
+    /// shown while the next ones are appended
+    Page(ListQuery, Page<ToDo>),
+    /// Outcomes of a batch, applied in order
+    Batch(Vec<BatchResult>),
 }
 
 impl Task {
@@ -85,6 +180,36 @@ impl Task {
                 return todos;
             }
             Self::Loaded(todos) => return todos.clone().into(),
+            Self::Page(query, page) => {
+                let mut new_todos = match query.cursor {
+                    Some(_) => (*todos).clone(),
+                    None => vec![],
+                };
+                // Todos added since the first page was loaded may show up again
+                for todo in &page.items {
+                    if !new_todos.iter().any(|known| known.id == todo.id) {
+                        new_todos.push(todo.clone());
+                    }
+                }
+                std::rc::Rc::new(new_todos)
+            }
+            Self::Batch(results) => {
+                let mut new_todos = (*todos).clone();
+                for result in results {
+                    match result {
+                        BatchResult::Create { todo } => new_todos.push(todo.clone()),
+                        BatchResult::Update { todo } => {
+                            if let Some(known) =
+                                new_todos.iter_mut().find(|known| known.id == todo.id)
+                            {
+                                *known = todo.clone();
+                            }
+                        }
+                        BatchResult::Delete { id } => new_todos.retain(|todo| todo.id != *id),
+                    }
+                }
+                std::rc::Rc::new(new_todos)
+            }
         }
     }
 }
@@ -94,6 +219,15 @@ pub enum TaskError {
     UpdateError,
     LoadError,
     GenericError(String),
+    /// The variants below mirror the `common::ApiError` sent by the server
+    BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    /// The todo changed on the server, this is its current copy
+    Stale(Box<ToDo>),
+    Invalid(String),
+    ServerError(String),
+    Unavailable(String),
 }
 
 impl TaskError {
@@ -104,14 +238,59 @@ impl TaskError {
             Self::UpdateError => "Failed to perform Update Operation".to_string(),
             Self::LoadError => "Failed to retireve ToDos".to_string(),
             Self::GenericError(err) => err.clone(),
+            Self::BadRequest(err) => format!("Bad request: {}", err),
+            Self::NotFound(err) => format!("Not found: {}", err),
+            Self::Conflict(err) => format!("Conflict: {}", err),
+            Self::Stale(todo) => format!("ToDo {} was changed elsewhere", todo.id),
+            Self::Invalid(err) => format!("Invalid ToDo: {}", err),
+            Self::ServerError(err) => format!("Server error: {}", err),
+            Self::Unavailable(err) => format!("Server unavailable: {}", err),
+        }
+    }
+}
+
+impl From<ApiError> for TaskError {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::BadRequest { message } => Self::BadRequest(message),
+            ApiError::NotFound { message } => Self::NotFound(message),
+            ApiError::Conflict { message } => Self::Conflict(message),
+            ApiError::VersionConflict { current, .. } => Self::Stale(current),
+            ApiError::Unprocessable { message } | ApiError::TooLarge { message } => {
+                Self::Invalid(message)
+            }
+            ApiError::Invalid { fields, .. } => Self::Invalid(
+                fields
+                    .iter()
+                    .map(|field| field.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            ApiError::Internal { message } => Self::ServerError(message),
+            ApiError::Unavailable { message } => Self::Unavailable(message),
         }
     }
 }
 
+/// Change refused because the todo changed elsewhere, kept until the user
+/// picks a version
+#[derive(PartialEq, Clone)]
+pub struct Conflict {
+    pub action: ActionType,
+    /// The todo as the user wanted it
+    pub mine: ToDo,
+    /// The todo as stored on the server
+    pub theirs: ToDo,
+}
+
 pub enum Msg {
     OnGoing(Task),
     Done(Task),
     Error(TaskError),
+    /// Shows a conflict, `None` once it is resolved
+    Conflict(Option<std::rc::Rc<Conflict>>),
+    /// Lists to switch between
+    Lists(Vec<TaskList>),
 }
 
 #[derive(PartialEq, Clone)]
@@ -119,6 +298,12 @@ pub struct ToDoState {
     pub todos: std::rc::Rc<Vec<ToDo>>,
     pub loading: bool,
     pub error: Option<String>,
+    /// Filters and sorting of the todos shown
+    pub query: ListQuery,
+    /// Cursor of the next page, `None` once everything is loaded
+    pub next_cursor: Option<String>,
+    pub lists: std::rc::Rc<Vec<TaskList>>,
+    pub conflict: Option<std::rc::Rc<Conflict>>,
 }
 
 impl ToDoState {
@@ -127,6 +312,10 @@ impl ToDoState {
             todos: std::rc::Rc::new(vec![]),
             loading: true,
             error: None,
+            query: ListQuery::default(),
+            next_cursor: None,
+            lists: std::rc::Rc::new(vec![]),
+            conflict: None,
         }
     }
 }
@@ -144,13 +333,31 @@ impl yew::Reducible for ToDoState {
             .into(),
             Msg::Done(task) => {
                 let todos = task.handle_task(self.todos.clone());
+                let (query, next_cursor) = match &task {
+                    Task::Page(query, page) => (query.first_page(), page.next_cursor.clone()),
+                    _ => (self.query.clone(), self.next_cursor.clone()),
+                };
                 Self {
                     todos,
                     loading: false,
                     error: None,
+                    query,
+                    next_cursor,
+                    ..(*self).clone()
                 }
                 .into()
             }
+            Msg::Conflict(conflict) => Self {
+                loading: false,
+                conflict,
+                ..(*self).clone()
+            }
+            .into(),
+            Msg::Lists(lists) => Self {
+                lists: lists.into(),
+                ..(*self).clone()
+            }
+            .into(),
 
             Msg::Error(task_error) => Self {
                 loading: false,
@@ -178,83 +385,527 @@ pub struct ToDoListProps {
     pub on_close: Callback<()>,
 }
 
-pub async fn get_todo() -> Result<Vec<ToDo>, &'static str> {
-    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_GET_TODO);
-    let response = Request::get(&path)
+#[derive(PartialEq, Properties, Clone)]
+pub struct ListControlsProps {
+    pub state: UseReducerHandle<ToDoState>,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct ListSwitcherProps {
+    pub state: UseReducerHandle<ToDoState>,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct TrashProps {
+    pub state: UseReducerHandle<ToDoState>,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct ConflictProps {
+    pub state: UseReducerHandle<ToDoState>,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct HistoryProps {
+    pub todo_id: usize,
+    pub state: UseReducerHandle<ToDoState>,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct NoteProps {
+    /// Markdown
+    pub note: String,
+}
+
+#[derive(PartialEq, Properties, Clone)]
+pub struct UpdateToDoProps {
+    pub state: UseReducerHandle<ToDoState>,
+    pub form_state: UseStateHandle<FormState>,
+    pub todo: ToDo,
+}
+
+/// Decodes the `ApiError` body of a failed response, `fallback` is used when
+/// the body is not one (e.g. a proxy answered instead of simple_server)
+async fn response_error(resp: &Response, fallback: TaskError) -> TaskError {
+    match resp.json::<ApiError>().await {
+        Ok(err) => err.into(),
+        Err(data) => {
+            info!(
+                "Unexpected error body with status {}: {}",
+                resp.status(),
+                data
+            );
+            fallback
+        }
+    }
+}
+
+/// Fetches one page of todos, `query.cursor` is the `next_cursor` of the
+/// previous page or `None` for the first one
+pub async fn get_todo(query: &ListQuery) -> Result<Page<ToDo>, TaskError> {
+    let path = format!(
+        "{}{}?{}",
+        SIMPLE_SERVER,
+        &SIMPLE_SERVER_GET_TODO,
+        query.to_query_string()
+    );
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
+
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    let todo_list_props: Page<ToDo> = response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })?;
+
+    Ok(todo_list_props)
+}
+
+/// Todos matching `query`, best hits first
+pub async fn search_todo(query: &SearchQuery) -> Result<Vec<SearchHit>, TaskError> {
+    let path = format!(
+        "{}{}?{}",
+        SIMPLE_SERVER,
+        &SIMPLE_SERVER_SEARCH,
+        query.to_query_string()
+    );
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
+
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// Every tag in use with its number of todos
+pub async fn get_tags() -> Result<Vec<TagCount>, TaskError> {
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_TAGS);
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
+
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+pub async fn get_lists() -> Result<Vec<TaskList>, TaskError> {
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_LISTS);
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
+
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// `key` is the `Idempotency-Key` of the user action, see `idempotency_key`
+pub async fn create_list(new_list: &NewTaskList, key: &str) -> Result<TaskList, TaskError> {
+    let list_json = serde_json::to_string(new_list)
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_LISTS);
+    let resp = send_idempotent(|| Request::post(&path), &list_json, key)
+        .await
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_STORE_TODO, data);
+            TaskError::AddError
+        })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::AddError).await);
+    }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// Deletes the list, the server moves its todos to the inbox
+pub async fn delete_list(id: usize) -> Result<(), TaskError> {
+    let path = format!("{}{}/{}", SIMPLE_SERVER, &SIMPLE_SERVER_LISTS, id);
+    let resp = Request::delete(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_DELETE_TODO, data);
+        TaskError::DeleteError
+    })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::DeleteError).await);
+    }
+
+    Ok(())
+}
+
+/// Trashed todos, the most recently deleted first
+pub async fn get_trash() -> Result<Vec<ToDo>, TaskError> {
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_TRASH);
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
+
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+pub async fn restore_todo(todo: &ToDo) -> Result<ToDo, TaskError> {
+    let path = format!(
+        "{}{}/{}/restore",
+        SIMPLE_SERVER, &SIMPLE_SERVER_TRASH, todo.id
+    );
+    let resp = Request::post(&path)
+        .header("If-Match", &if_match(todo.version))
         .send()
         .await
-        .map_err(|_| FAILED_TO_RETRIEVE_TODO)?;
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
 
-    info!("Wrong data for parsing: {:?}", response);
-    let todo_list_props: Vec<ToDo> = response.json().await.map_err(|data| {
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
+    }
+
+    resp.json().await.map_err(|data| {
         info!("Wrong data for parsing: {}", data);
-        UNABLE_TO_PARSE_FROM_JSON
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// Deletes every trashed todo for good
+pub async fn empty_trash() -> Result<(), TaskError> {
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_TRASH);
+    let resp = Request::delete(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_DELETE_TODO, data);
+        TaskError::DeleteError
     })?;
 
-    Ok(todo_list_props)
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::DeleteError).await);
+    }
+
+    Ok(())
 }
 
-pub async fn delete_todo(todo: &ToDo) -> Result<(), &'static str> {
-    let todo_json = serde_json::to_string(todo).map_err(|_| common::UNABLE_TO_PARSE_DATA)?;
+/// Kept revisions of a todo, the oldest first
+pub async fn get_history(id: usize) -> Result<Vec<Revision>, TaskError> {
+    let path = format!("{}{}/{}/history", SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, id);
+    let response = Request::get(&path).send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_RETRIEVE_TODO, data);
+        TaskError::LoadError
+    })?;
 
-    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_DELETE_TODO);
+    if !response.ok() {
+        return Err(response_error(&response, TaskError::LoadError).await);
+    }
+
+    response.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// `version` is the one of the todo the revision was picked from
+pub async fn revert_todo(id: usize, rev: u64, version: u64) -> Result<ToDo, TaskError> {
+    let path = format!(
+        "{}{}/{}/revert/{}",
+        SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, id, rev
+    );
     let resp = Request::post(&path)
-        .header("Content-Type", "application/json")
-        .body(todo_json)
+        .header("If-Match", &if_match(version))
         .send()
         .await
-        .map_err(|_| FAILED_TO_DELETE_TODO)?;
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
 
-    if resp.ok() {
-        Ok(())
-    } else {
-        Err(FAILED_TO_DELETE_TODO)
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
     }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
 }
-pub async fn store_todo(todo: &ToDo) -> Result<(), &'static str> {
-    let todo_json = serde_json::to_string(todo).map_err(|_| common::UNABLE_TO_PARSE_DATA)?;
 
-    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_STORE_TODO);
+pub async fn move_todo(todo: &ToDo, list_id: usize) -> Result<ToDo, TaskError> {
+    let move_json = serde_json::to_string(&MoveToDo { list_id })
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}{}/{}/move", SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, todo.id);
     let resp = Request::post(&path)
         .header("Content-Type", "application/json")
-        .body(todo_json)
+        .header("If-Match", &if_match(todo.version))
+        .body(move_json)
         .send()
         .await
-        .map_err(|_| FAILED_TO_STORE_TODO)?;
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
 
-    if resp.ok() {
-        Ok(())
-    } else {
-        Err(FAILED_TO_STORE_TODO)
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
     }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
 }
 
-// This Is for PlaceHolding must be changed later
-pub async fn update_todo(todo: &ToDo) -> Result<(), &'static str> {
-    let todo_json = serde_json::to_string(todo).map_err(|_| common::UNABLE_TO_PARSE_DATA)?;
+pub async fn delete_todo(todo: &ToDo) -> Result<(), TaskError> {
+    let todo_json = serde_json::to_string(todo)
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
 
     let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_DELETE_TODO);
     let resp = Request::post(&path)
         .header("Content-Type", "application/json")
+        .header("If-Match", &if_match(todo.version))
         .body(todo_json)
         .send()
         .await
-        .map_err(|_| FAILED_TO_STORE_TODO)?;
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_DELETE_TODO, data);
+            TaskError::DeleteError
+        })?;
 
     if resp.ok() {
         Ok(())
     } else {
-        Err(FAILED_TO_DELETE_TODO)
+        Err(response_error(&resp, TaskError::DeleteError).await)
+    }
+}
+
+/// Applies every operation or, when one fails, none of them. `key` is the
+/// `Idempotency-Key` of the batch
+pub async fn batch(ops: Vec<BatchOp>, key: &str) -> Result<Vec<BatchResult>, TaskError> {
+    let batch_json = serde_json::to_string(&Batch { ops })
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}{}/batch", SIMPLE_SERVER, &SIMPLE_SERVER_TODOS);
+    let resp = send_idempotent(|| Request::post(&path), &batch_json, key)
+        .await
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
     }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// The server assigns the id, so the stored copy is what should be shown.
+/// `key` is the `Idempotency-Key` of the user action, see `idempotency_key`
+pub async fn store_todo(todo: &ToDo, key: &str) -> Result<ToDo, TaskError> {
+    let todo_json = serde_json::to_string(&NewToDo::from(todo))
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}{}", SIMPLE_SERVER, &SIMPLE_SERVER_STORE_TODO);
+    let resp = send_idempotent(|| Request::post(&path), &todo_json, key)
+        .await
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_STORE_TODO, data);
+            TaskError::AddError
+        })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::AddError).await);
+    }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+pub async fn update_todo(todo: &ToDo) -> Result<ToDo, TaskError> {
+    let patch_json = serde_json::to_string(&ToDoPatch::from(todo))
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}{}/{}", SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, todo.id);
+    let resp = Request::patch(&path)
+        .header("Content-Type", "application/json")
+        .header("If-Match", &if_match(todo.version))
+        .body(patch_json)
+        .send()
+        .await
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
+    }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+pub async fn toggle_todo(todo: &ToDo) -> Result<ToDo, TaskError> {
+    let path = format!(
+        "{}{}/{}/toggle",
+        SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, todo.id
+    );
+    let resp = Request::post(&path)
+        .header("If-Match", &if_match(todo.version))
+        .send()
+        .await
+        .map_err(|data| {
+            info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+            TaskError::UpdateError
+        })?;
+
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
+    }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+/// Sends an edit of the checklist of a todo at `version`, the server answers
+/// with the whole `ToDo`
+async fn checklist_request(
+    request: Request,
+    version: u64,
+    body: Option<String>,
+) -> Result<ToDo, TaskError> {
+    let request = request.header("If-Match", &if_match(version));
+    let request = match body {
+        Some(body) => request
+            .header("Content-Type", "application/json")
+            .body(body),
+        None => request,
+    };
+    let resp = request.send().await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+        TaskError::UpdateError
+    })?;
+
+    checklist_response(resp).await
+}
+
+/// The `ToDo` a checklist edit answers with
+async fn checklist_response(resp: Response) -> Result<ToDo, TaskError> {
+    if !resp.ok() {
+        return Err(response_error(&resp, TaskError::UpdateError).await);
+    }
+
+    resp.json().await.map_err(|data| {
+        info!("Wrong data for parsing: {}", data);
+        TaskError::GenericError(UNABLE_TO_PARSE_FROM_JSON.to_string())
+    })
+}
+
+fn items_path(todo_id: usize) -> String {
+    format!(
+        "{}{}/{}/items",
+        SIMPLE_SERVER, &SIMPLE_SERVER_TODOS, todo_id
+    )
+}
+
+/// `key` is the `Idempotency-Key` of the user action, see `idempotency_key`
+pub async fn add_item(
+    todo_id: usize,
+    version: u64,
+    text: String,
+    key: &str,
+) -> Result<ToDo, TaskError> {
+    let body = serde_json::to_string(&NewChecklistItem { text })
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = items_path(todo_id);
+    let if_match = if_match(version);
+    let build = || Request::post(&path).header("If-Match", &if_match);
+    let resp = send_idempotent(build, &body, key).await.map_err(|data| {
+        info!("{}: {}", FAILED_TO_UPDATE_TODO, data);
+        TaskError::UpdateError
+    })?;
+
+    checklist_response(resp).await
+}
+
+pub async fn update_item(
+    todo_id: usize,
+    version: u64,
+    item_id: usize,
+    patch: ChecklistItemPatch,
+) -> Result<ToDo, TaskError> {
+    let body = serde_json::to_string(&patch)
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+
+    let path = format!("{}/{}", items_path(todo_id), item_id);
+    checklist_request(Request::patch(&path), version, Some(body)).await
+}
+
+pub async fn delete_item(todo_id: usize, version: u64, item_id: usize) -> Result<ToDo, TaskError> {
+    let path = format!("{}/{}", items_path(todo_id), item_id);
+    checklist_request(Request::delete(&path), version, None).await
+}
+
+/// `ids` lists every item of the checklist in its new order
+pub async fn reorder_items(
+    todo_id: usize,
+    version: u64,
+    ids: Vec<usize>,
+) -> Result<ToDo, TaskError> {
+    let body = serde_json::to_string(&ChecklistOrder { ids })
+        .map_err(|_| TaskError::GenericError(common::UNABLE_TO_PARSE_DATA.to_string()))?;
+    checklist_request(Request::put(&items_path(todo_id)), version, Some(body)).await
 }
 
-pub async fn manage_action_request(
-    action_type: ActionType,
-    todo: ToDo,
-) -> Result<(), &'static str> {
+/// Returns the `ToDo` as it should be shown after the action took place
+pub async fn manage_action_request(action_type: ActionType, todo: ToDo) -> Result<ToDo, TaskError> {
     match action_type {
-        ActionType::Add => store_todo(&todo).await,
-        ActionType::Delete => delete_todo(&todo).await,
+        ActionType::Add => store_todo(&todo, &idempotency_key()).await,
+        ActionType::Delete => delete_todo(&todo).await.map(|_| todo),
         ActionType::Update => update_todo(&todo).await,
+        ActionType::Toggle => toggle_todo(&todo).await,
     }
 }
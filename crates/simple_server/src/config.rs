@@ -0,0 +1,399 @@
+use crate::cors::cors_layer;
+use chrono::TimeDelta;
+use clap::{Parser, ValueEnum};
+use deadpool_redis::redis::{ConnectionInfo, IntoConnectionInfo};
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tower_http::cors::CorsLayer;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_CONFIG_FILE: &str = "simple_server.toml";
+const DEFAULT_BIND: &str = "127.0.0.1:3000";
+const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1";
+const DEFAULT_POOL_SIZE: usize = 16;
+const DEFAULT_LOG_FILTER: &str = "info";
+/// `trunk serve` default address
+const DEFAULT_ALLOWED_ORIGINS: [&str; 2] = ["http://127.0.0.1:8080", "http://localhost:8080"];
+const DEFAULT_ALLOWED_METHODS: [&str; 5] = ["GET", "POST", "PUT", "PATCH", "DELETE"];
+const DEFAULT_ALLOWED_HEADERS: [&str; 3] = ["content-type", "if-match", "idempotency-key"];
+const DEFAULT_CORS_MAX_AGE_SECS: u64 = 3600;
+/// Fits the longest valid `ToDo`
+const DEFAULT_MAX_BODY_BYTES: usize = 256 * 1024;
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+/// A day, long enough for any client retry
+const DEFAULT_IDEMPOTENCY_WINDOW_SECS: u64 = 24 * 3600;
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Redis,
+    /// Keeps todos in process, they are lost on shutdown
+    Memory,
+}
+
+/// One layer of settings, every field is optional so that the layers can be
+/// stacked: defaults < TOML file < environment < command line
+#[derive(Parser, Deserialize, Default, Debug)]
+#[command(name = "simple_server", about = "Backend of the ToDo Yew app")]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    /// TOML file to read, defaults to `simple_server.toml` when it exists
+    #[arg(long, env = "SIMPLE_SERVER_CONFIG")]
+    #[serde(skip)]
+    config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 127.0.0.1:3000
+    #[arg(long, env = "SIMPLE_SERVER_BIND")]
+    bind: Option<String>,
+
+    #[arg(long, env = "SIMPLE_SERVER_REDIS_URL")]
+    redis_url: Option<String>,
+
+    #[arg(long, env = "SIMPLE_SERVER_REDIS_DB")]
+    redis_db: Option<i64>,
+
+    /// Maximum number of Redis connections
+    #[arg(long, env = "SIMPLE_SERVER_POOL_SIZE")]
+    pool_size: Option<usize>,
+
+    /// `tracing` filter directives, e.g. `info,simple_server=debug`
+    #[arg(long, env = "RUST_LOG")]
+    log_filter: Option<String>,
+
+    #[arg(long, value_enum, env = "SIMPLE_SERVER_STORE")]
+    storage: Option<StorageBackend>,
+
+    /// Comma separated list of origins the browser client may call from,
+    /// `*` allows any origin
+    #[arg(long, env = "SIMPLE_SERVER_ALLOWED_ORIGINS", value_delimiter = ',')]
+    allowed_origins: Option<Vec<String>>,
+
+    /// Comma separated list of methods allowed cross origin, `*` allows any
+    #[arg(long, env = "SIMPLE_SERVER_ALLOWED_METHODS", value_delimiter = ',')]
+    allowed_methods: Option<Vec<String>>,
+
+    /// Comma separated list of request headers allowed cross origin, `*` allows any
+    #[arg(long, env = "SIMPLE_SERVER_ALLOWED_HEADERS", value_delimiter = ',')]
+    allowed_headers: Option<Vec<String>>,
+
+    /// How long browsers may cache a preflight response
+    #[arg(long, env = "SIMPLE_SERVER_CORS_MAX_AGE_SECS")]
+    cors_max_age_secs: Option<u64>,
+
+    /// Largest request body accepted, larger ones get a 413
+    #[arg(long, env = "SIMPLE_SERVER_MAX_BODY_BYTES")]
+    max_body_bytes: Option<usize>,
+
+    /// Days a deleted todo stays in the trash before it is deleted for good
+    #[arg(long, env = "SIMPLE_SERVER_TRASH_RETENTION_DAYS")]
+    trash_retention_days: Option<i64>,
+
+    /// Revisions kept per todo, older ones are dropped
+    #[arg(long, env = "SIMPLE_SERVER_HISTORY_LIMIT")]
+    history_limit: Option<usize>,
+
+    /// How long the response to a request with an `Idempotency-Key` is
+    /// replayed for repeated keys
+    #[arg(long, env = "SIMPLE_SERVER_IDEMPOTENCY_WINDOW_SECS")]
+    idempotency_window_secs: Option<u64>,
+
+    /// Rewrites the todos stored with an older schema version in the
+    /// background after startup, reads upgrade them either way
+    #[arg(long, env = "SIMPLE_SERVER_MIGRATE_RECORDS")]
+    migrate_records: Option<bool>,
+}
+
+impl Layer {
+    /// Fills the fields not set in `self` from `lower`
+    fn or(self, lower: Layer) -> Layer {
+        Layer {
+            config: self.config.or(lower.config),
+            bind: self.bind.or(lower.bind),
+            redis_url: self.redis_url.or(lower.redis_url),
+            redis_db: self.redis_db.or(lower.redis_db),
+            pool_size: self.pool_size.or(lower.pool_size),
+            log_filter: self.log_filter.or(lower.log_filter),
+            storage: self.storage.or(lower.storage),
+            allowed_origins: self.allowed_origins.or(lower.allowed_origins),
+            allowed_methods: self.allowed_methods.or(lower.allowed_methods),
+            allowed_headers: self.allowed_headers.or(lower.allowed_headers),
+            cors_max_age_secs: self.cors_max_age_secs.or(lower.cors_max_age_secs),
+            max_body_bytes: self.max_body_bytes.or(lower.max_body_bytes),
+            trash_retention_days: self.trash_retention_days.or(lower.trash_retention_days),
+            history_limit: self.history_limit.or(lower.history_limit),
+            idempotency_window_secs: self
+                .idempotency_window_secs
+                .or(lower.idempotency_window_secs),
+            migrate_records: self.migrate_records.or(lower.migrate_records),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "invalid config file {}: {}", path.display(), e),
+            Self::Invalid(field, e) => write!(f, "invalid `{}`: {}", field, e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub redis: ConnectionInfo,
+    pub pool_size: usize,
+    pub log_filter: String,
+    pub storage: StorageBackend,
+    pub allowed_origins: Vec<String>,
+    pub cors: CorsLayer,
+    pub max_body_bytes: usize,
+    pub trash_retention: TimeDelta,
+    pub history_limit: usize,
+    pub idempotency_window: Duration,
+    pub migrate_records: bool,
+}
+
+impl Config {
+    /// Reads the command line, the environment and the config file
+    pub fn load() -> Result<Self, ConfigError> {
+        let cli = Layer::parse();
+
+        let (path, required) = match &cli.config {
+            Some(path) => (path.clone(), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| ConfigError::Parse(path, e))?,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => Layer::default(),
+            Err(e) => return Err(ConfigError::Read(path, e)),
+        };
+
+        Self::from_layer(cli.or(file))
+    }
+
+    fn from_layer(layer: Layer) -> Result<Self, ConfigError> {
+        let bind = layer
+            .bind
+            .as_deref()
+            .unwrap_or(DEFAULT_BIND)
+            .parse()
+            .map_err(|e| ConfigError::Invalid("bind", format!("{}", e)))?;
+
+        let mut redis = layer
+            .redis_url
+            .as_deref()
+            .unwrap_or(DEFAULT_REDIS_URL)
+            .into_connection_info()
+            .map_err(|e| ConfigError::Invalid("redis_url", e.to_string()))?;
+        if let Some(db) = layer.redis_db {
+            if db < 0 {
+                return Err(ConfigError::Invalid(
+                    "redis_db",
+                    "must not be negative".into(),
+                ));
+            }
+            redis.redis.db = db;
+        }
+
+        let pool_size = layer.pool_size.unwrap_or(DEFAULT_POOL_SIZE);
+        if pool_size == 0 {
+            return Err(ConfigError::Invalid(
+                "pool_size",
+                "must be at least 1".into(),
+            ));
+        }
+
+        let log_filter = layer.log_filter.unwrap_or(DEFAULT_LOG_FILTER.to_string());
+        EnvFilter::try_new(&log_filter)
+            .map_err(|e| ConfigError::Invalid("log_filter", e.to_string()))?;
+
+        let allowed_origins = layer
+            .allowed_origins
+            .unwrap_or(DEFAULT_ALLOWED_ORIGINS.map(String::from).to_vec());
+        let cors = cors_layer(
+            &allowed_origins,
+            &layer
+                .allowed_methods
+                .unwrap_or(DEFAULT_ALLOWED_METHODS.map(String::from).to_vec()),
+            &layer
+                .allowed_headers
+                .unwrap_or(DEFAULT_ALLOWED_HEADERS.map(String::from).to_vec()),
+            Duration::from_secs(layer.cors_max_age_secs.unwrap_or(DEFAULT_CORS_MAX_AGE_SECS)),
+        )?;
+
+        let trash_retention_days = layer
+            .trash_retention_days
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+        let trash_retention = TimeDelta::try_days(trash_retention_days)
+            .filter(|retention| *retention >= TimeDelta::zero())
+            .ok_or_else(|| {
+                ConfigError::Invalid(
+                    "trash_retention_days",
+                    format!("{} is out of range", trash_retention_days),
+                )
+            })?;
+
+        let history_limit = layer.history_limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+        if history_limit == 0 {
+            return Err(ConfigError::Invalid(
+                "history_limit",
+                "must be at least 1".into(),
+            ));
+        }
+
+        let idempotency_window_secs = layer
+            .idempotency_window_secs
+            .unwrap_or(DEFAULT_IDEMPOTENCY_WINDOW_SECS);
+        if idempotency_window_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "idempotency_window_secs",
+                "must be at least 1".into(),
+            ));
+        }
+
+        Ok(Self {
+            bind,
+            redis,
+            pool_size,
+            log_filter,
+            storage: layer.storage.unwrap_or(StorageBackend::Redis),
+            allowed_origins,
+            cors,
+            max_body_bytes: layer.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES),
+            trash_retention,
+            history_limit,
+            idempotency_window: Duration::from_secs(idempotency_window_secs),
+            migrate_records: layer.migrate_records.unwrap_or(false),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_field(layer: Layer) -> Option<&'static str> {
+        match Config::from_layer(layer) {
+            Err(ConfigError::Invalid(field, _)) => Some(field),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn higher_layers_win() {
+        let cli = Layer {
+            bind: Some("0.0.0.0:80".into()),
+            pool_size: Some(4),
+            ..Layer::default()
+        };
+        let file = Layer {
+            bind: Some("127.0.0.1:8000".into()),
+            storage: Some(StorageBackend::Memory),
+            pool_size: None,
+            ..Layer::default()
+        };
+
+        let layer = cli.or(file);
+        assert_eq!(layer.bind.as_deref(), Some("0.0.0.0:80"));
+        assert_eq!(layer.pool_size, Some(4));
+        assert_eq!(layer.storage, Some(StorageBackend::Memory));
+        assert_eq!(layer.redis_url, None);
+    }
+
+    #[test]
+    fn reads_toml_layers() {
+        let layer: Layer = toml::from_str(
+            r#"
+            bind = "0.0.0.0:80"
+            storage = "memory"
+            allowed_origins = ["https://todo.example"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(layer.bind.as_deref(), Some("0.0.0.0:80"));
+        assert_eq!(layer.storage, Some(StorageBackend::Memory));
+        assert_eq!(
+            layer.allowed_origins,
+            Some(vec!["https://todo.example".to_string()])
+        );
+
+        assert!(toml::from_str::<Layer>("port = 80").is_err());
+    }
+
+    #[test]
+    fn defaults_fill_an_empty_layer() {
+        let config = Config::from_layer(Layer::default()).unwrap();
+        assert_eq!(config.bind, DEFAULT_BIND.parse().unwrap());
+        assert_eq!(config.pool_size, DEFAULT_POOL_SIZE);
+        assert_eq!(config.storage, StorageBackend::Redis);
+        assert_eq!(config.allowed_origins, DEFAULT_ALLOWED_ORIGINS);
+        assert_eq!(config.trash_retention, TimeDelta::days(30));
+        assert!(!config.migrate_records);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let layers = [
+            (
+                "bind",
+                Layer {
+                    bind: Some("localhost".into()),
+                    ..Layer::default()
+                },
+            ),
+            (
+                "redis_db",
+                Layer {
+                    redis_db: Some(-1),
+                    ..Layer::default()
+                },
+            ),
+            (
+                "pool_size",
+                Layer {
+                    pool_size: Some(0),
+                    ..Layer::default()
+                },
+            ),
+            (
+                "trash_retention_days",
+                Layer {
+                    trash_retention_days: Some(-1),
+                    ..Layer::default()
+                },
+            ),
+            (
+                "history_limit",
+                Layer {
+                    history_limit: Some(0),
+                    ..Layer::default()
+                },
+            ),
+            (
+                "idempotency_window_secs",
+                Layer {
+                    idempotency_window_secs: Some(0),
+                    ..Layer::default()
+                },
+            ),
+        ];
+        for (field, layer) in layers {
+            assert_eq!(invalid_field(layer), Some(field));
+        }
+    }
+}
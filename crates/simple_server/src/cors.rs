@@ -0,0 +1,69 @@
+use crate::config::ConfigError;
+use axum::http::{
+    HeaderName, HeaderValue, Method,
+    header::{ETAG, RETRY_AFTER},
+};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+
+/// Entry that allows everything in an origin, method or header list
+const WILDCARD: &str = "*";
+
+/// Builds the CORS policy of the router, preflight requests are answered by
+/// the layer itself so the JSON POSTs of the Yew app go through. The ETag of
+/// a todo is readable cross origin
+pub fn cors_layer(
+    origins: &[String],
+    methods: &[String],
+    headers: &[String],
+    max_age: Duration,
+) -> Result<CorsLayer, ConfigError> {
+    let allow_origin = if origins.iter().any(|origin| origin == WILDCARD) {
+        AllowOrigin::from(Any)
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin.trim_end_matches('/')).map_err(|e| {
+                    ConfigError::Invalid("allowed_origins", format!("{}: {}", origin, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let allow_methods = if methods.iter().any(|method| method == WILDCARD) {
+        AllowMethods::from(Any)
+    } else {
+        let methods = methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|e| {
+                    ConfigError::Invalid("allowed_methods", format!("{}: {}", method, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowMethods::list(methods)
+    };
+
+    let allow_headers = if headers.iter().any(|header| header == WILDCARD) {
+        AllowHeaders::from(Any)
+    } else {
+        let headers = headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes()).map_err(|e| {
+                    ConfigError::Invalid("allowed_headers", format!("{}: {}", header, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowHeaders::list(headers)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .expose_headers([ETAG, RETRY_AFTER])
+        .max_age(max_age))
+}
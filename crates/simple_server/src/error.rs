@@ -0,0 +1,75 @@
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use common::ApiError;
+use log::error;
+
+/// Wraps `common::ApiError` so it can be returned from the handlers
+#[derive(Debug)]
+pub struct AppError(pub ApiError);
+
+impl From<ApiError> for AppError {
+    fn from(err: ApiError) -> Self {
+        Self(err)
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        let err = match rejection {
+            JsonRejection::JsonDataError(_) => ApiError::unprocessable(rejection.body_text()),
+            _ if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                ApiError::too_large(rejection.body_text())
+            }
+            _ => ApiError::bad_request(rejection.body_text()),
+        };
+        Self(err)
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        Self(ApiError::bad_request(rejection.body_text()))
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self(ApiError::bad_request(rejection.body_text()))
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.0.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        if status.is_server_error() {
+            error!("{}", self.0);
+        }
+
+        (status, Json(self.0)).into_response()
+    }
+}
+
+pub type ApiResult<T> = Result<T, AppError>;
+
+/// `Json` extractor that rejects with an `ApiError` body
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/// `Path` extractor that rejects with an `ApiError` body
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct AppPath<T>(pub T);
+
+/// `Query` extractor that rejects with an `ApiError` body
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
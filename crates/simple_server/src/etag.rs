@@ -0,0 +1,99 @@
+use crate::error::AppError;
+use axum::{
+    Json, async_trait,
+    extract::FromRequestParts,
+    http::{
+        HeaderValue,
+        header::{ETAG, IF_MATCH},
+        request::Parts,
+    },
+    response::{IntoResponse, Response},
+};
+use common::{ApiError, ToDo};
+
+/// Entity tag of a todo, its quoted version
+pub fn etag(version: u64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version)).expect("a quoted number is a header value")
+}
+
+/// Version an `If-Match` header asks for, `None` without one or for `*`.
+/// Weak tags are refused, `If-Match` compares tags strongly
+pub struct IfMatch(pub Option<u64>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IF_MATCH) else {
+            return Ok(Self(None));
+        };
+        let invalid = || ApiError::bad_request("`If-Match` must hold one strong ETag of a ToDo");
+
+        let value = value.to_str().map_err(|_| invalid())?.trim();
+        if value == "*" {
+            return Ok(Self(None));
+        }
+        let version = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .and_then(|version| version.parse().ok())
+            .ok_or_else(invalid)?;
+
+        Ok(Self(Some(version)))
+    }
+}
+
+/// A todo sent with its ETag
+pub struct Tagged(pub ToDo);
+
+impl IntoResponse for Tagged {
+    fn into_response(self) -> Response {
+        ([(ETAG, etag(self.0.version))], Json(self.0)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn if_match(value: Option<&str>) -> Result<Option<u64>, u16> {
+        let mut request = Request::builder();
+        if let Some(value) = value {
+            request = request.header(IF_MATCH, value);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+
+        IfMatch::from_request_parts(&mut parts, &())
+            .await
+            .map(|IfMatch(version)| version)
+            .map_err(|e| e.0.status())
+    }
+
+    #[test]
+    fn tags_are_quoted_versions() {
+        assert_eq!(etag(7), "\"7\"");
+    }
+
+    #[tokio::test]
+    async fn parses_if_match() {
+        assert_eq!(if_match(None).await, Ok(None));
+        assert_eq!(if_match(Some("*")).await, Ok(None));
+        assert_eq!(if_match(Some("\"7\"")).await, Ok(Some(7)));
+        assert_eq!(if_match(Some(" \"7\" ")).await, Ok(Some(7)));
+    }
+
+    #[tokio::test]
+    async fn rejects_weak_tags() {
+        assert_eq!(if_match(Some("W/\"7\"")).await, Err(400));
+        assert_eq!(if_match(Some("w/\"7\"")).await, Err(400));
+    }
+
+    #[tokio::test]
+    async fn rejects_other_tags() {
+        for value in ["7", "\"7", "\"seven\"", "\"-1\"", "\"1\", \"2\"", ""] {
+            assert_eq!(if_match(Some(value)).await, Err(400), "{:?}", value);
+        }
+    }
+}
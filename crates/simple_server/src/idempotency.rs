@@ -0,0 +1,392 @@
+use crate::error::{ApiResult, AppError};
+use crate::store::IdempotencyStore;
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{
+        HeaderName, HeaderValue, Method, StatusCode,
+        header::{CONTENT_TYPE, ETAG, RETRY_AFTER},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use common::ApiError;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on replayed responses
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+const MAX_KEY_LEN: usize = 255;
+/// Seconds a client waits before trying again a request still being handled
+const RETRY_AFTER_SECS: &str = "1";
+/// How long a claimed key stays pending, should its request never finish
+const PENDING_TTL: Duration = Duration::from_secs(30);
+const INVALID_KEY: &str = "`Idempotency-Key` must be 1 to 255 visible ASCII characters";
+const KEY_IN_USE: &str = "A request with this `Idempotency-Key` is still being handled";
+const KEY_REUSED: &str = "`Idempotency-Key` was already used for another request";
+/// Response headers replayed along the body
+const KEPT_HEADERS: [HeaderName; 2] = [CONTENT_TYPE, ETAG];
+
+/// What a store keeps under an `Idempotency-Key`, tagged by `state` in JSON
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum Recorded {
+    /// The first request with the key is still being handled
+    Pending { fingerprint: String },
+    /// Response to the first request with the key
+    Done {
+        fingerprint: String,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: String,
+    },
+}
+
+impl Recorded {
+    fn fingerprint(&self) -> &str {
+        match self {
+            Self::Pending { fingerprint } | Self::Done { fingerprint, .. } => fingerprint,
+        }
+    }
+
+    /// Response to send again for a request with `fingerprint`
+    fn replay(self, fingerprint: &str) -> ApiResult<Response> {
+        if self.fingerprint() != fingerprint {
+            return Err(ApiError::unprocessable(KEY_REUSED).into());
+        }
+        let Self::Done {
+            status,
+            headers,
+            body,
+            ..
+        } = self
+        else {
+            let retry_after = HeaderValue::from_static(RETRY_AFTER_SECS);
+            let conflict = AppError(ApiError::conflict(KEY_IN_USE));
+            return Ok(([(RETRY_AFTER, retry_after)], conflict).into_response());
+        };
+
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = StatusCode::from_u16(status).map_err(|_| {
+            error!("Recorded response has status {}", status);
+            ApiError::internal(common::UNABLE_TO_PARSE_DATA)
+        })?;
+        for (name, value) in headers {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::try_from(name), HeaderValue::try_from(value))
+            {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+
+        Ok(response)
+    }
+}
+
+/// Hash telling apart requests sent with the same key
+fn fingerprint(method: &Method, path: &str, body: &[u8]) -> String {
+    let digest = Sha256::new()
+        .chain_update(method.as_str())
+        .chain_update(b" ")
+        .chain_update(path)
+        .chain_update(b"\n")
+        .chain_update(body)
+        .finalize();
+
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// State of the `replay` middleware
+#[derive(Clone)]
+pub struct Idempotency {
+    pub store: Arc<dyn IdempotencyStore>,
+    /// How long a response is replayed
+    pub window: Duration,
+    pub max_body_bytes: usize,
+}
+
+/// Middleware for the create and batch routes. The first response to a
+/// request with an `Idempotency-Key` is recorded for the window and sent
+/// again for every later request with the key, unless it was a server error.
+/// While the first request is handled, later ones get a 409 with
+/// `Retry-After`. Requests without the header go through untouched
+pub async fn replay(
+    State(idempotency): State<Idempotency>,
+    request: Request,
+    next: Next,
+) -> Response {
+    idempotency
+        .handle(request, next)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+impl Idempotency {
+    async fn handle(&self, request: Request, next: Next) -> ApiResult<Response> {
+        let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+            return Ok(next.run(request).await);
+        };
+        let key = key
+            .to_str()
+            .ok()
+            .filter(|key| {
+                !key.is_empty()
+                    && key.len() <= MAX_KEY_LEN
+                    && key.bytes().all(|byte| byte.is_ascii_graphic())
+            })
+            .ok_or_else(|| ApiError::bad_request(INVALID_KEY))?
+            .to_string();
+
+        let (parts, body) = request.into_parts();
+        let body = to_bytes(body, self.max_body_bytes)
+            .await
+            .map_err(|_| ApiError::too_large("Request body is too large"))?;
+        let fingerprint = fingerprint(&parts.method, parts.uri.path(), &body);
+
+        let pending = Recorded::Pending {
+            fingerprint: fingerprint.clone(),
+        };
+        if let Some(recorded) = self.store.claim_key(&key, &pending, PENDING_TTL).await? {
+            info!("Replaying the response for Idempotency-Key {:?}", key);
+            return recorded.replay(&fingerprint);
+        }
+        let claim = Claim {
+            store: Some(self.store.clone()),
+            key: key.clone(),
+        };
+
+        let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+        let response = self.record(&key, fingerprint, response).await;
+        claim.settle();
+
+        Ok(response)
+    }
+
+    /// Keeps `response` under the claimed `key`, or frees the key when the
+    /// request may be tried again
+    async fn record(&self, key: &str, fingerprint: String, response: Response) -> Response {
+        if response.status().is_server_error() {
+            self.release(key).await;
+            return response;
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = match to_bytes(body, usize::MAX).await {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Cannot read the response to record it: {}", e);
+                self.release(key).await;
+                return AppError(ApiError::internal("Failed to send the response")).into_response();
+            }
+        };
+        let Ok(text) = std::str::from_utf8(&body) else {
+            self.release(key).await;
+            return Response::from_parts(parts, Body::from(body));
+        };
+
+        let headers = KEPT_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_string()))
+            })
+            .collect();
+        let done = Recorded::Done {
+            fingerprint,
+            status: parts.status.as_u16(),
+            headers,
+            body: text.to_string(),
+        };
+        if let Err(e) = self.store.remember_key(key, &done, self.window).await {
+            error!(
+                "Cannot record the response for Idempotency-Key {:?}: {}",
+                key, e
+            );
+            self.release(key).await;
+        }
+
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    /// Frees `key` so that the request can be sent again with it
+    async fn release(&self, key: &str) {
+        release(self.store.as_ref(), key).await;
+    }
+}
+
+async fn release(store: &dyn IdempotencyStore, key: &str) {
+    if let Err(e) = store.release_key(key).await {
+        error!("Cannot release Idempotency-Key {:?}: {}", key, e);
+    }
+}
+
+/// A claimed key, freed when dropped before its response is recorded, as
+/// happens when the client goes away mid-request
+struct Claim {
+    /// `None` once the response is recorded
+    store: Option<Arc<dyn IdempotencyStore>>,
+    key: String,
+}
+
+impl Claim {
+    fn settle(mut self) {
+        self.store = None;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(store) = self.store.take() {
+            let key = std::mem::take(&mut self.key);
+            tokio::spawn(async move { release(store.as_ref(), &key).await });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    fn done(fingerprint: &str) -> Recorded {
+        Recorded::Done {
+            fingerprint: fingerprint.to_string(),
+            status: 201,
+            headers: vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("etag".to_string(), "\"1\"".to_string()),
+            ],
+            body: "{\"id\":1}".to_string(),
+        }
+    }
+
+    #[test]
+    fn fingerprints_tell_requests_apart() {
+        let post = fingerprint(&Method::POST, "/todos", b"{}");
+        assert_eq!(post, fingerprint(&Method::POST, "/todos", b"{}"));
+        assert_eq!(post.len(), 64);
+        assert!(post.bytes().all(|byte| byte.is_ascii_hexdigit()));
+
+        assert_ne!(post, fingerprint(&Method::PUT, "/todos", b"{}"));
+        assert_ne!(post, fingerprint(&Method::POST, "/lists", b"{}"));
+        assert_ne!(post, fingerprint(&Method::POST, "/todos", b"{ }"));
+        // The separators keep the path and body apart
+        assert_ne!(
+            fingerprint(&Method::POST, "/a", b"b"),
+            fingerprint(&Method::POST, "/ab", b"")
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_the_recorded_response() {
+        let response = done("abc").replay("abc").unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let headers = response.headers();
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[ETAG], "\"1\"");
+        assert_eq!(headers[IDEMPOTENT_REPLAYED], "true");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"{\"id\":1}");
+    }
+
+    #[test]
+    fn asks_to_retry_while_pending() {
+        let pending = Recorded::Pending {
+            fingerprint: "abc".to_string(),
+        };
+        let response = pending.replay("abc").unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(response.headers()[RETRY_AFTER], RETRY_AFTER_SECS);
+        assert!(!response.headers().contains_key(IDEMPOTENT_REPLAYED));
+    }
+
+    #[test]
+    fn refuses_another_request_with_the_key() {
+        let pending = Recorded::Pending {
+            fingerprint: "abc".to_string(),
+        };
+        for recorded in [pending, done("abc")] {
+            let error = recorded.replay("def").unwrap_err();
+            assert_eq!(error.0, ApiError::unprocessable(KEY_REUSED));
+        }
+    }
+
+    #[test]
+    fn records_are_tagged_by_state() {
+        let pending = Recorded::Pending {
+            fingerprint: "abc".to_string(),
+        };
+        let json = serde_json::to_string(&pending).unwrap();
+        assert_eq!(json, r#"{"state":"pending","fingerprint":"abc"}"#);
+        assert_eq!(serde_json::from_str::<Recorded>(&json).unwrap(), pending);
+
+        let json = serde_json::to_string(&done("abc")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Recorded>(&json).unwrap(),
+            done("abc")
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_claims_expire() {
+        let store = MemoryStore::new(10);
+        let pending = Recorded::Pending {
+            fingerprint: "abc".to_string(),
+        };
+        let ttl = Duration::from_millis(20);
+        assert_eq!(store.claim_key("key", &pending, ttl).await.unwrap(), None);
+        assert_eq!(
+            store.claim_key("key", &pending, ttl).await.unwrap(),
+            Some(pending.clone())
+        );
+
+        // The request that claimed the key never finished
+        tokio::time::sleep(ttl * 2).await;
+        assert_eq!(store.claim_key("key", &pending, ttl).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn dropped_claims_free_their_key() {
+        let store = Arc::new(MemoryStore::new(10));
+        let pending = Recorded::Pending {
+            fingerprint: "abc".to_string(),
+        };
+        for key in ["dropped", "settled"] {
+            store.claim_key(key, &pending, PENDING_TTL).await.unwrap();
+        }
+
+        drop(Claim {
+            store: Some(store.clone()),
+            key: "dropped".to_string(),
+        });
+        Claim {
+            store: Some(store.clone()),
+            key: "settled".to_string(),
+        }
+        .settle();
+        tokio::task::yield_now().await;
+
+        assert_eq!(
+            store
+                .claim_key("dropped", &pending, PENDING_TTL)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            store
+                .claim_key("settled", &pending, PENDING_TTL)
+                .await
+                .unwrap(),
+            Some(pending)
+        );
+    }
+}
@@ -1,131 +1,779 @@
-use axum::{Json, Router, extract::State, response::IntoResponse, routing::get, routing::post};
-use common::ToDo;
-use deadpool_redis::{Config, Pool, Runtime, redis};
-use log::{error, info};
-use serde_json;
+mod config;
+mod cors;
+mod error;
+mod etag;
+mod idempotency;
+mod search;
+mod store;
+
+use axum::extract::{DefaultBodyLimit, FromRef, State};
+use axum::handler::Handler;
+use axum::middleware::from_fn_with_state;
+use axum::{Json, Router, routing::get, routing::patch, routing::post};
+use chrono::{TimeDelta, Utc};
+use common::batch::{Batch, BatchOp, BatchResult, MAX_BATCH_OPS};
+use common::checklist::{ChecklistItem, ChecklistItemPatch, ChecklistOrder, NewChecklistItem};
+use common::history::Revision;
+use common::lists::{
+    INBOX_LIST_ID, MoveToDo, NewTaskList, TaskList, TaskListPatch, is_valid_colour,
+};
+use common::query::{ListQuery, Page};
+use common::search::{SearchHit, SearchQuery};
+use common::tags::{TagCount, TagMerge, TagRename, normalize_tag, normalize_tags};
+use common::{ApiError, NewToDo, ToDo, ToDoPatch};
+use config::{Config, StorageBackend};
+use deadpool_redis::{Pool, PoolConfig, Runtime};
+use error::{ApiResult, AppError, AppJson, AppPath, AppQuery};
+use etag::{IfMatch, Tagged};
+use idempotency::Idempotency;
+use log::{debug, error, info, warn};
+use search::{SearchIndex, SharedSearch};
+use std::collections::BTreeMap;
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::Duration;
+use store::{
+    BatchOutcome, IdempotencyStore, MemoryStore, RedisStore, TodoStore, UNABLE_TO_CONNECT,
+    move_patch,
+};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::EnvFilter;
 
-const REDIS_CONN: &'static str = "redis://127.0.0.1";
-const SERVER_CONN: &'static str = "127.0.0.1:3000";
+const TODO_NOT_FOUND: &str = "ToDo not found";
+const LIST_NOT_FOUND: &str = "List not found";
+const ITEM_NOT_FOUND: &str = "Checklist item not found";
+const REVISION_NOT_FOUND: &str = "Revision not found";
+const INBOX_IS_FIXED: &str = "The Inbox cannot be changed";
+/// How often the trash is checked for todos past their retention
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+type SharedStore = Arc<dyn TodoStore>;
+type SharedKeys = Arc<dyn IdempotencyStore>;
 
-const UNABLE_TO_CONNECT: &'static str = "Unable to connect to Redis";
-const FAILED_TO_STORE_DATA: &'static str = "Failed to store data";
-const FAILED_TO_DELETE_DATA: &'static str = "Failed to delete data";
-const FAILED_TO_RETRIEVE_DATA: &'static str = "Failed to retireve data";
+/// State of the router, handlers extract the parts they need
+#[derive(Clone, FromRef)]
+struct AppState {
+    store: SharedStore,
+    search: SharedSearch,
+}
 
-fn get_redis_conn() -> Result<Pool, &'static str> {
-    let cfg = Config::from_url(REDIS_CONN);
+fn get_redis_conn(config: &Config) -> Result<Pool, ApiError> {
+    let mut cfg = deadpool_redis::Config::from_connection_info(config.redis.clone());
+    cfg.pool = Some(PoolConfig::new(config.pool_size));
     let pool = cfg
         .create_pool(Some(Runtime::Tokio1))
-        .map_err(|_| UNABLE_TO_CONNECT)?;
+        .map_err(|_| ApiError::unavailable(UNABLE_TO_CONNECT))?;
 
     Ok(pool)
 }
-async fn not_found() -> impl IntoResponse {
-    (axum::http::StatusCode::NOT_FOUND, "Route not found")
+
+/// The configured store, as the todo and the `Idempotency-Key` storage
+async fn get_store(config: &Config) -> Result<(SharedStore, SharedKeys), ApiError> {
+    match config.storage {
+        StorageBackend::Memory => {
+            warn!("Using the in-memory store, data is lost on shutdown");
+            let store = Arc::new(MemoryStore::new(config.history_limit));
+            Ok((store.clone(), store))
+        }
+        StorageBackend::Redis => {
+            info!(
+                "Using Redis at {} (db {})",
+                config.redis.addr, config.redis.redis.db
+            );
+            let store = RedisStore::new(get_redis_conn(config)?, config.history_limit);
+            store.import_legacy_keys().await?;
+            if config.migrate_records {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = store.migrate_records().await {
+                        error!("Schema migration stopped: {}", e);
+                    }
+                });
+            }
+            let store = Arc::new(store);
+            Ok((store.clone(), store))
+        }
+    }
 }
 
-async fn store_todo(
-    State(pool): State<Arc<Pool>>,
-    Json(payload): Json<ToDo>,
-) -> Result<(), &'static str> {
-    let mut conn = pool.get().await.map_err(|_| UNABLE_TO_CONNECT)?;
-    let json = serde_json::to_string(&payload).map_err(|_| common::UNABLE_TO_PARSE_DATA)?;
-
-    let _: () = redis::cmd("SET")
-        .arg(&payload.id)
-        .arg(&json)
-        .query_async(&mut conn)
-        .await
-        .map_err(|e| {
-            error!("{}: {}", FAILED_TO_STORE_DATA, e);
-            FAILED_TO_STORE_DATA
-        })?;
+async fn not_found() -> AppError {
+    ApiError::not_found("Route not found").into()
+}
+
+/// Fails unless `list_id` is the inbox or a stored list
+async fn ensure_list(store: &SharedStore, list_id: usize) -> ApiResult<()> {
+    if list_id != INBOX_LIST_ID && store.get_list(list_id).await?.is_none() {
+        return Err(ApiError::bad_request(format!("Unknown list {}", list_id)).into());
+    }
 
     Ok(())
 }
 
+async fn store_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppJson(payload): AppJson<NewToDo>,
+) -> ApiResult<Tagged> {
+    payload.validate().map_err(ApiError::invalid)?;
+    ensure_list(&store, payload.list_id).await?;
+    let todo = store.create(payload).await?;
+    search.insert(todo.clone());
+
+    info!("Stored Data: {:?}", todo);
+
+    Ok(Tagged(todo))
+}
+
 async fn delete_todo(
-    State(pool): State<Arc<Pool>>,
-    Json(payload): Json<ToDo>,
-) -> Result<(), &'static str> {
-    let mut conn = pool.get().await.map_err(|_| UNABLE_TO_CONNECT)?;
-    let _: () = redis::cmd("DEL")
-        .arg(&payload.id)
-        .query_async(&mut conn)
-        .await
-        .map_err(|e| {
-            error!("{}: {:?}", FAILED_TO_DELETE_DATA, e);
-            return FAILED_TO_DELETE_DATA;
-        })?;
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    IfMatch(expected): IfMatch,
+    AppJson(payload): AppJson<ToDo>,
+) -> ApiResult<()> {
+    if !store.delete(payload.id, expected).await? {
+        return Err(ApiError::not_found(TODO_NOT_FOUND).into());
+    }
+    search.remove(payload.id);
 
-    info!("Deleted `Data: {:?}", payload);
+    info!("Moved to the trash: {:?}", payload);
 
     Ok(())
 }
 
-async fn get_todo(State(pool): State<Arc<Pool>>) -> Result<Json<Vec<ToDo>>, &'static str> {
-    let mut conn = pool.get().await.map_err(|_| UNABLE_TO_CONNECT)?;
-
-    let mut cursor: u64 = 0;
-    let mut todo_vec: Vec<ToDo> = vec![];
-
-    info!("GetTodo called");
-    loop {
-        let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
-            .cursor_arg(cursor)
-            .query_async(&mut conn)
-            .await
-            .map_err(|e| {
-                error!("{}: {}", FAILED_TO_RETRIEVE_DATA, e);
-                FAILED_TO_RETRIEVE_DATA
-            })?;
-
-        for key in keys {
-            let todo_str: String = redis::cmd("GET")
-                .arg(key)
-                .query_async(&mut conn)
-                .await
-                .map_err(|e| {
-                    error!("{}: {}", FAILED_TO_RETRIEVE_DATA, e);
-                    FAILED_TO_STORE_DATA
-                })?;
-            // BAD BAD BAD
-            let todo: ToDo = serde_json::from_str(&todo_str).unwrap();
-            todo_vec.push(todo);
+/// Trashed todos, the most recently deleted first
+async fn get_trash(State(store): State<SharedStore>) -> ApiResult<Json<Vec<ToDo>>> {
+    Ok(Json(store.trash().await?))
+}
+
+/// Takes a todo out of the trash, back to the inbox when its list is gone
+async fn restore_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+) -> ApiResult<Tagged> {
+    let mut todo = store
+        .restore(id, expected)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    if ensure_list(&store, todo.list_id).await.is_err() {
+        todo = store
+            .update(id, move_patch(INBOX_LIST_ID))
+            .await?
+            .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    }
+    search.insert(todo.clone());
+
+    info!("Restored from the trash: {:?}", todo);
+
+    Ok(Tagged(todo))
+}
+
+async fn empty_trash(State(store): State<SharedStore>) -> ApiResult<()> {
+    let purged = store.purge(None).await?;
+
+    info!("Emptied the trash, {} ToDo(s) deleted", purged.len());
+
+    Ok(())
+}
+
+/// Deletes for good, every `PURGE_INTERVAL`, the todos trashed longer than
+/// `retention` ago
+fn spawn_purge(store: SharedStore, retention: TimeDelta) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match store.purge(Some(Utc::now() - retention)).await {
+                Ok(purged) if !purged.is_empty() => {
+                    info!("Purged {} ToDo(s) from the trash", purged.len())
+                }
+                Ok(_) => {}
+                Err(e) => error!("Purging the trash failed: {}", e),
+            }
         }
-        cursor = new_cursor;
+    });
+}
+
+/// Creates the next occurrence of the recurring `todo` just completed and
+/// clears its rule, the occurrence carries it on from there so reopening and
+/// completing again creates no second one
+async fn complete_recurring(
+    store: &SharedStore,
+    search: &SharedSearch,
+    todo: ToDo,
+) -> ApiResult<ToDo> {
+    if let Some(next) = todo.next_occurrence() {
+        let next = store.create(next).await?;
+        search.insert(next.clone());
+
+        info!("Created next occurrence of {}: {:?}", todo.id, next);
+    }
+    let patch = ToDoPatch {
+        recurrence: Some(None),
+        ..ToDoPatch::default()
+    };
+
+    store
+        .update(todo.id, patch)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND).into())
+}
+
+/// Validates and applies `patch` to the todo `id`, then reindexes it.
+/// Completing a recurring todo creates its next occurrence
+async fn patch_todo(
+    store: &SharedStore,
+    search: &SharedSearch,
+    id: usize,
+    patch: ToDoPatch,
+) -> ApiResult<Tagged> {
+    if let Some(list_id) = patch.list_id {
+        ensure_list(store, list_id).await?;
+    }
+    let current = store
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    current.check_version(patch.version)?;
+    current.validate_patch(&patch).map_err(ApiError::invalid)?;
+    let mut todo = store
+        .update(id, patch)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
 
-        if cursor == 0 {
-            break;
+    if !current.completed && todo.completed && todo.recurrence.is_some() {
+        todo = complete_recurring(store, search, todo).await?;
+    }
+    search.insert(todo.clone());
+
+    info!("Updated Data: {:?}", todo);
+
+    Ok(Tagged(todo))
+}
+
+/// Kept revisions of todo `id`, the oldest first
+async fn get_history(
+    State(store): State<SharedStore>,
+    AppPath(id): AppPath<usize>,
+) -> ApiResult<Json<Vec<Revision>>> {
+    let history = store.history(id).await?;
+    if history.is_empty() {
+        return Err(ApiError::not_found(TODO_NOT_FOUND).into());
+    }
+
+    Ok(Json(history))
+}
+
+/// Brings todo `id` back to revision `rev`, in its current list when the one
+/// it had then is gone. The revert is recorded as a revision of its own and
+/// never creates a recurring todo's next occurrence
+async fn revert_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath((id, rev)): AppPath<(usize, u64)>,
+    IfMatch(expected): IfMatch,
+) -> ApiResult<Tagged> {
+    let current = store
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    let revision = store
+        .history(id)
+        .await?
+        .into_iter()
+        .find(|revision| revision.rev == rev)
+        .ok_or_else(|| ApiError::not_found(REVISION_NOT_FOUND))?;
+
+    let mut patch = ToDoPatch {
+        version: expected,
+        ..revision.revert_patch()
+    };
+    let list_id = revision.todo.list_id;
+    if list_id != current.list_id && ensure_list(&store, list_id).await.is_ok() {
+        patch.list_id = Some(list_id);
+    }
+    current.validate_patch(&patch).map_err(ApiError::invalid)?;
+    let todo = store
+        .update(id, patch)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    search.insert(todo.clone());
+
+    info!("Reverted ToDo {} to revision {}: {:?}", id, rev, todo);
+
+    Ok(Tagged(todo))
+}
+
+/// Applies every operation of the batch or none of them, returns the outcome
+/// of each. Recurring todos the batch leaves completed get their next
+/// occurrence afterwards, which is not among the results, and are returned
+/// with their rule cleared
+async fn batch_todos(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppJson(batch): AppJson<Batch>,
+) -> ApiResult<Json<Vec<BatchResult>>> {
+    if batch.ops.len() > MAX_BATCH_OPS {
+        return Err(ApiError::bad_request(format!(
+            "At most {} operations per batch",
+            MAX_BATCH_OPS
+        ))
+        .into());
+    }
+    for (index, op) in batch.ops.iter().enumerate() {
+        let list_id = match op {
+            BatchOp::Create { todo } => Some(todo.list_id),
+            BatchOp::Update { patch, .. } => patch.list_id,
+            BatchOp::Delete { .. } => None,
+        };
+        if let Some(list_id) = list_id
+            && ensure_list(&store, list_id).await.is_err()
+        {
+            return Err(ApiError::bad_request(format!(
+                "Operation {}: unknown list {}",
+                index, list_id
+            ))
+            .into());
+        }
+    }
+    let BatchOutcome {
+        mut results,
+        before,
+    } = store.batch(batch).await?;
+
+    // Where the batch left each todo, `None` once trashed
+    let mut touched: BTreeMap<usize, Option<&ToDo>> = BTreeMap::new();
+    for result in &results {
+        match result {
+            BatchResult::Create { todo } | BatchResult::Update { todo } => {
+                touched.insert(todo.id, Some(todo))
+            }
+            BatchResult::Delete { id } => touched.insert(*id, None),
+        };
+    }
+    let mut rolled = vec![];
+    for (id, todo) in touched {
+        let was_completed = before.get(&id).is_some_and(|old| old.completed);
+        match todo {
+            Some(todo) if !was_completed && todo.completed && todo.recurrence.is_some() => {
+                rolled.push(complete_recurring(&store, &search, todo.clone()).await?);
+            }
+            Some(todo) => search.insert(todo.clone()),
+            None => search.remove(id),
+        }
+    }
+    // The last result of each such todo gets its current version
+    for todo in rolled {
+        search.insert(todo.clone());
+        let last = results.iter_mut().rev().find_map(|result| match result {
+            BatchResult::Create { todo: old } | BatchResult::Update { todo: old }
+                if old.id == todo.id =>
+            {
+                Some(old)
+            }
+            _ => None,
+        });
+        if let Some(last) = last {
+            *last = todo;
         }
     }
 
-    info!("Retrieved Data: {:?}", todo_vec);
-    Ok(Json(todo_vec))
+    info!("Applied a batch of {} operation(s)", results.len());
+
+    Ok(Json(results))
 }
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env()
-                .or_else(|_| EnvFilter::try_new("info"))
-                .unwrap(),
-        )
-        .init();
-    let redis_conn = get_redis_conn().unwrap();
+async fn read_todo(
+    State(store): State<SharedStore>,
+    AppPath(id): AppPath<usize>,
+) -> ApiResult<Tagged> {
+    let todo = store
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+
+    Ok(Tagged(todo))
+}
+
+/// `If-Match` takes precedence over the `version` of the patch
+async fn update_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+    AppJson(mut patch): AppJson<ToDoPatch>,
+) -> ApiResult<Tagged> {
+    patch.version = expected.or(patch.version);
+
+    patch_todo(&store, &search, id, patch).await
+}
+
+async fn complete_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+) -> ApiResult<Tagged> {
+    let patch = ToDoPatch {
+        version: expected,
+        ..ToDoPatch::completed(true)
+    };
+
+    patch_todo(&store, &search, id, patch).await
+}
+
+async fn toggle_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+) -> ApiResult<Tagged> {
+    let todo = store
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    let patch = ToDoPatch {
+        version: expected,
+        ..ToDoPatch::completed(!todo.completed)
+    };
+
+    patch_todo(&store, &search, id, patch).await
+}
+
+async fn get_todo(
+    State(store): State<SharedStore>,
+    AppQuery(query): AppQuery<ListQuery>,
+) -> ApiResult<Json<Page<ToDo>>> {
+    info!("GetTodo called with {:?}", query);
+    let page = store.list(&query).await?;
+
+    debug!(
+        "Retrieved {} ToDo(s), next cursor {:?}",
+        page.items.len(),
+        page.next_cursor
+    );
+    Ok(Json(page))
+}
+
+async fn move_todo(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+    AppJson(payload): AppJson<MoveToDo>,
+) -> ApiResult<Tagged> {
+    let patch = ToDoPatch {
+        version: expected,
+        ..move_patch(payload.list_id)
+    };
+
+    patch_todo(&store, &search, id, patch).await
+}
+
+/// Replaces the checklist of todo `id` with what `edit` makes of it, unless
+/// the todo changes in the meantime or does not have `expected`
+async fn edit_checklist(
+    store: &SharedStore,
+    search: &SharedSearch,
+    id: usize,
+    expected: Option<u64>,
+    edit: impl FnOnce(&ToDo) -> ApiResult<Vec<ChecklistItem>>,
+) -> ApiResult<Tagged> {
+    let todo = store
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(TODO_NOT_FOUND))?;
+    todo.check_version(expected)?;
+    let patch = ToDoPatch {
+        checklist: Some(edit(&todo)?),
+        version: Some(todo.version),
+        ..ToDoPatch::default()
+    };
+
+    patch_todo(store, search, id, patch).await
+}
+
+async fn add_item(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+    AppJson(payload): AppJson<NewChecklistItem>,
+) -> ApiResult<Tagged> {
+    edit_checklist(&store, &search, id, expected, |todo| {
+        Ok(todo.with_item(payload.text))
+    })
+    .await
+}
+
+async fn update_item(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath((id, item_id)): AppPath<(usize, usize)>,
+    IfMatch(expected): IfMatch,
+    AppJson(patch): AppJson<ChecklistItemPatch>,
+) -> ApiResult<Tagged> {
+    edit_checklist(&store, &search, id, expected, |todo| {
+        todo.with_item_patched(item_id, patch)
+            .ok_or_else(|| ApiError::not_found(ITEM_NOT_FOUND).into())
+    })
+    .await
+}
+
+async fn delete_item(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath((id, item_id)): AppPath<(usize, usize)>,
+    IfMatch(expected): IfMatch,
+) -> ApiResult<Tagged> {
+    edit_checklist(&store, &search, id, expected, |todo| {
+        todo.without_item(item_id)
+            .ok_or_else(|| ApiError::not_found(ITEM_NOT_FOUND).into())
+    })
+    .await
+}
+
+async fn reorder_items(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+    IfMatch(expected): IfMatch,
+    AppJson(order): AppJson<ChecklistOrder>,
+) -> ApiResult<Tagged> {
+    edit_checklist(&store, &search, id, expected, |todo| {
+        todo.reordered(&order.ids).ok_or_else(|| {
+            ApiError::bad_request("`ids` must list every checklist item once").into()
+        })
+    })
+    .await
+}
+
+/// Rejects empty names and colours other than `#rrggbb`
+fn check_list(name: Option<&str>, colour: Option<&str>) -> ApiResult<()> {
+    if name.is_some_and(|name| name.trim().is_empty()) {
+        return Err(ApiError::bad_request("List name must not be empty").into());
+    }
+    if let Some(colour) = colour.filter(|colour| !is_valid_colour(colour)) {
+        return Err(ApiError::bad_request(format!("Invalid colour `{}`", colour)).into());
+    }
+
+    Ok(())
+}
+
+/// The inbox followed by the stored lists, by position
+async fn get_lists(State(store): State<SharedStore>) -> ApiResult<Json<Vec<TaskList>>> {
+    let mut lists = store.lists().await?;
+    lists.sort_by_key(|list| (list.position, list.id));
+    lists.insert(0, TaskList::inbox());
+
+    Ok(Json(lists))
+}
+
+async fn create_list(
+    State(store): State<SharedStore>,
+    AppJson(payload): AppJson<NewTaskList>,
+) -> ApiResult<Json<TaskList>> {
+    check_list(Some(&payload.name), payload.colour.as_deref())?;
+    let list = store.create_list(payload).await?;
+
+    info!("Created list: {:?}", list);
+
+    Ok(Json(list))
+}
+
+async fn update_list(
+    State(store): State<SharedStore>,
+    AppPath(id): AppPath<usize>,
+    AppJson(patch): AppJson<TaskListPatch>,
+) -> ApiResult<Json<TaskList>> {
+    if id == INBOX_LIST_ID {
+        return Err(ApiError::bad_request(INBOX_IS_FIXED).into());
+    }
+    check_list(patch.name.as_deref(), patch.colour.as_deref())?;
+    let list = store
+        .update_list(id, patch)
+        .await?
+        .ok_or_else(|| ApiError::not_found(LIST_NOT_FOUND))?;
+
+    info!("Updated list: {:?}", list);
+
+    Ok(Json(list))
+}
+
+/// Deletes the list, its todos go back to the inbox
+async fn delete_list(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(id): AppPath<usize>,
+) -> ApiResult<()> {
+    if id == INBOX_LIST_ID {
+        return Err(ApiError::bad_request(INBOX_IS_FIXED).into());
+    }
+    let moved = store
+        .delete_list(id)
+        .await?
+        .ok_or_else(|| ApiError::not_found(LIST_NOT_FOUND))?;
+    for todo in &moved {
+        search.insert(todo.clone());
+    }
+
+    info!(
+        "Deleted list {}, moved {} ToDo(s) to the Inbox",
+        id,
+        moved.len()
+    );
+
+    Ok(())
+}
+
+async fn list_tags(State(store): State<SharedStore>) -> ApiResult<Json<Vec<TagCount>>> {
+    Ok(Json(store.tags().await?))
+}
+
+/// Moves the todos tagged with one of `from` to `into`, returns the todos
+/// changed
+async fn retag(
+    store: &SharedStore,
+    search: &SharedSearch,
+    from: &[String],
+    into: &str,
+) -> ApiResult<Json<Vec<ToDo>>> {
+    let into = normalize_tag(into)
+        .ok_or_else(|| ApiError::bad_request(format!("Invalid tag `{}`", into)))?;
+    let from: Vec<String> = normalize_tags(from)
+        .into_iter()
+        .filter(|tag| *tag != into)
+        .collect();
+    if from.is_empty() {
+        return Err(ApiError::bad_request("No tag to move").into());
+    }
+
+    let todos = store.merge_tags(&from, &into).await?;
+    if todos.is_empty() {
+        return Err(ApiError::not_found("Tag not found").into());
+    }
+    for todo in &todos {
+        search.insert(todo.clone());
+    }
+
+    info!("Moved {} ToDo(s) from {:?} to {}", todos.len(), from, into);
+    Ok(Json(todos))
+}
+
+async fn rename_tag(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppPath(tag): AppPath<String>,
+    AppJson(rename): AppJson<TagRename>,
+) -> ApiResult<Json<Vec<ToDo>>> {
+    retag(&store, &search, &[tag], &rename.to).await
+}
+
+async fn merge_tags(
+    State(store): State<SharedStore>,
+    State(search): State<SharedSearch>,
+    AppJson(merge): AppJson<TagMerge>,
+) -> ApiResult<Json<Vec<ToDo>>> {
+    retag(&store, &search, &merge.from, &merge.into).await
+}
+
+async fn search_todo(
+    State(search): State<SharedSearch>,
+    AppQuery(query): AppQuery<SearchQuery>,
+) -> ApiResult<Json<Vec<SearchHit>>> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::bad_request("`q` must not be empty").into());
+    }
+    let hits = search.search(&query.q, query.limit());
+
+    debug!("Search {:?} found {} ToDo(s)", query.q, hits.len());
+    Ok(Json(hits))
+}
+
+async fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let (store, keys) = get_store(&config).await?;
+    let index = SearchIndex::rebuild(store.as_ref()).await?;
+    info!("Indexed {} ToDo(s) for search", index.len());
+    spawn_purge(store.clone(), config.trash_retention);
+    let idempotent = from_fn_with_state(
+        Idempotency {
+            store: keys,
+            window: config.idempotency_window,
+            max_body_bytes: config.max_body_bytes,
+        },
+        idempotency::replay,
+    );
+    let state = AppState {
+        store,
+        search: SharedSearch::new(index),
+    };
     let app = Router::new()
-        .route("/store_todo", post(store_todo))
+        .route("/store_todo", post(store_todo.layer(idempotent.clone())))
         .route("/delete_todo", post(delete_todo))
         .route("/get_todo", get(get_todo))
+        .route("/todos/batch", post(batch_todos.layer(idempotent.clone())))
+        .route(
+            "/todos/:id",
+            get(read_todo).patch(update_todo).put(update_todo),
+        )
+        .route("/todos/:id/complete", post(complete_todo))
+        .route("/todos/:id/toggle", post(toggle_todo))
+        .route("/todos/:id/move", post(move_todo))
+        .route("/todos/:id/history", get(get_history))
+        .route("/todos/:id/revert/:rev", post(revert_todo))
+        .route(
+            "/todos/:id/items",
+            post(add_item.layer(idempotent.clone())).put(reorder_items),
+        )
+        .route(
+            "/todos/:id/items/:item",
+            patch(update_item).delete(delete_item),
+        )
+        .route("/lists", get(get_lists).post(create_list.layer(idempotent)))
+        .route("/lists/:id", patch(update_list).delete(delete_list))
+        .route("/search", get(search_todo))
+        .route("/tags", get(list_tags))
+        .route("/tags/merge", post(merge_tags))
+        .route("/tags/:tag/rename", post(rename_tag))
+        .route("/trash", get(get_trash).delete(empty_trash))
+        .route("/trash/:id/restore", post(restore_todo))
         .fallback(not_found)
-        .with_state(Arc::new(redis_conn))
+        .with_state(state)
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(config.cors.clone())
         .layer(TraceLayer::new_for_http());
 
-    info!("Starting Simple Server on: {:?}", SERVER_CONN);
-    let listener = tokio::net::TcpListener::bind(SERVER_CONN).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    info!("Allowed origins: {:?}", config.allowed_origins);
+    info!("Starting Simple Server on: {:?}", config.bind);
+    let listener = tokio::net::TcpListener::bind(config.bind)
+        .await
+        .map_err(|e| format!("cannot listen on {}: {}", config.bind, e))?;
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            // The configured filter is unknown, errors are logged either way
+            tracing_subscriber::fmt().init();
+            error!("simple_server: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(&config.log_filter))
+        .init();
+
+    if let Err(e) = run(config).await {
+        error!("simple_server: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
 }
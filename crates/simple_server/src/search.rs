@@ -0,0 +1,345 @@
+use crate::store::TodoStore;
+use common::query::{ListQuery, MAX_PAGE_LIMIT};
+use common::search::SearchHit;
+use common::{ApiError, ToDo};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Weight of a token equal to the query term
+const EXACT_WEIGHT: f32 = 1.0;
+/// Weight of a token the query term is a prefix of
+const PREFIX_WEIGHT: f32 = 0.7;
+/// Weight of a token one edit away from the query term, halved per extra edit
+const FUZZY_WEIGHT: f32 = 0.5;
+
+/// Index shared by the handlers, a panic while it was locked leaves it usable
+#[derive(Clone)]
+pub struct SharedSearch(Arc<RwLock<SearchIndex>>);
+
+impl SharedSearch {
+    pub fn new(index: SearchIndex) -> Self {
+        Self(Arc::new(RwLock::new(index)))
+    }
+
+    pub fn insert(&self, todo: ToDo) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(todo);
+    }
+
+    pub fn remove(&self, id: usize) {
+        self.0
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+    }
+
+    pub fn search(&self, q: &str, limit: usize) -> Vec<SearchHit> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .search(q, limit)
+    }
+}
+
+/// Inverted index over `todo_info`, kept in sync by the handlers on every
+/// create, update and delete
+#[derive(Default)]
+pub struct SearchIndex {
+    /// token -> id -> byte ranges of the token in the note
+    postings: BTreeMap<String, HashMap<usize, Vec<Range<usize>>>>,
+    todos: HashMap<usize, ToDo>,
+}
+
+/// Lowercase words of `text` with their byte range
+fn tokenize(text: &str) -> impl Iterator<Item = (String, Range<usize>)> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(move |word| {
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (word.to_lowercase(), start..start + word.len())
+        })
+}
+
+/// Edits tolerated for a query term, short terms must match exactly
+fn max_edits(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Levenshtein distance, `None` as soon as it exceeds `max`
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|min| *min > max) {
+            return None;
+        }
+        previous = current;
+    }
+
+    previous.last().copied().filter(|distance| *distance <= max)
+}
+
+impl SearchIndex {
+    /// Indexes every todo of `store`
+    pub async fn rebuild(store: &dyn TodoStore) -> Result<Self, ApiError> {
+        let mut index = Self::default();
+        let mut query = ListQuery {
+            limit: Some(MAX_PAGE_LIMIT),
+            ..ListQuery::default()
+        };
+        loop {
+            let page = store.list(&query).await?;
+            for todo in page.items {
+                index.insert(todo);
+            }
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.todos.len()
+    }
+
+    /// Adds `todo`, replacing what was indexed for the same id
+    pub fn insert(&mut self, todo: ToDo) {
+        self.remove(todo.id);
+        for (token, range) in tokenize(&todo.todo_info) {
+            self.postings
+                .entry(token)
+                .or_default()
+                .entry(todo.id)
+                .or_default()
+                .push(range);
+        }
+        self.todos.insert(todo.id, todo);
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        let Some(todo) = self.todos.remove(&id) else {
+            return;
+        };
+        for (token, _) in tokenize(&todo.todo_info) {
+            if let Some(postings) = self.postings.get_mut(&token) {
+                postings.remove(&id);
+                if postings.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    /// Indexed tokens matching `term` with the weight of the match
+    fn candidates(&self, term: &str) -> Vec<(&str, f32)> {
+        let mut candidates: Vec<(&str, f32)> = self
+            .postings
+            .range(term.to_string()..)
+            .take_while(|(token, _)| token.starts_with(term))
+            .map(|(token, _)| {
+                let weight = if token == term {
+                    EXACT_WEIGHT
+                } else {
+                    PREFIX_WEIGHT
+                };
+                (token.as_str(), weight)
+            })
+            .collect();
+
+        let max = max_edits(term);
+        if max > 0 {
+            for token in self.postings.keys() {
+                if token.starts_with(term) {
+                    continue;
+                }
+                if let Some(distance) = edit_distance(term, token, max) {
+                    let weight = FUZZY_WEIGHT / (1 << (distance - 1)) as f32;
+                    candidates.push((token.as_str(), weight));
+                }
+            }
+        }
+
+        candidates
+    }
+
+    /// Ranks the todos matching the words of `q`, a todo matches a word when
+    /// one of its words is equal to it, starts with it or is a typo away
+    pub fn search(&self, q: &str, limit: usize) -> Vec<SearchHit> {
+        let terms: Vec<String> = tokenize(q).map(|(term, _)| term).collect();
+        let total = self.todos.len() as f32;
+
+        // id -> (score, query terms matched, ranges)
+        let mut hits: HashMap<usize, (f32, usize, Vec<Range<usize>>)> = HashMap::new();
+        for term in &terms {
+            let mut term_scores: HashMap<usize, f32> = HashMap::new();
+            for (token, weight) in self.candidates(term) {
+                let postings = &self.postings[token];
+                let idf = (1.0 + total / postings.len() as f32).ln();
+                for (id, ranges) in postings {
+                    let tf = (1.0 + ranges.len() as f32).ln();
+                    let score = term_scores.entry(*id).or_default();
+                    *score = score.max(weight * tf * idf);
+                    hits.entry(*id)
+                        .or_default()
+                        .2
+                        .extend(ranges.iter().cloned());
+                }
+            }
+            for (id, score) in term_scores {
+                let hit = hits.entry(id).or_default();
+                hit.0 += score;
+                hit.1 += 1;
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = hits
+            .into_iter()
+            .map(|(id, (score, matched, mut ranges))| {
+                ranges.sort_by_key(|range| range.start);
+                ranges.dedup();
+                // Todos matching every word of the query rank first
+                let coverage = matched as f32 / terms.len() as f32;
+                SearchHit {
+                    todo: self.todos[&id].clone(),
+                    score: score * coverage * coverage,
+                    matches: ranges,
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.todo.id.cmp(&b.todo.id)));
+        hits.truncate(limit);
+
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(notes: &[&str]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for (id, note) in notes.iter().enumerate() {
+            index.insert(ToDo::new(note, id + 1));
+        }
+        index
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<usize> {
+        hits.iter().map(|hit| hit.todo.id).collect()
+    }
+
+    #[test]
+    fn tokens_are_lowercase_words_with_byte_ranges() {
+        let text = "Buy MILK, then café-crème!";
+        let tokens: Vec<(String, Range<usize>)> = tokenize(text).collect();
+        let words: Vec<&str> = tokens.iter().map(|(token, _)| token.as_str()).collect();
+        assert_eq!(words, ["buy", "milk", "then", "café", "crème"]);
+        let originals: Vec<&str> = tokens
+            .iter()
+            .map(|(_, range)| &text[range.clone()])
+            .collect();
+        assert_eq!(originals, ["Buy", "MILK", "then", "café", "crème"]);
+        assert_eq!(tokenize(" ,.! ").count(), 0);
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("milk", "milk", 0), Some(0));
+        assert_eq!(edit_distance("milk", "silk", 1), Some(1));
+        assert_eq!(edit_distance("milk", "mlk", 1), Some(1));
+        assert_eq!(edit_distance("crème", "creme", 1), Some(1));
+        assert_eq!(edit_distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(edit_distance("kitten", "sitting", 2), None);
+        // Lengths too far apart
+        assert_eq!(edit_distance("milk", "milkshake", 2), None);
+        // Every cell of the second row is already over the maximum
+        assert_eq!(edit_distance("abcd", "wxyz", 1), None);
+    }
+
+    #[test]
+    fn short_terms_must_match_exactly() {
+        assert_eq!(max_edits("mlk"), 0);
+        assert_eq!(max_edits("milke"), 1);
+        assert_eq!(max_edits("grocries"), 2);
+        assert!(index(&["Buy milk"]).search("mlk", 10).is_empty());
+    }
+
+    #[test]
+    fn matches_prefixes() {
+        let index = index(&["Water the plants", "Plan the trip", "Wash the car"]);
+        assert_eq!(ids(&index.search("pla", 10)), [1, 2]);
+        // The exact word ranks above the longer one
+        assert_eq!(ids(&index.search("plan", 10)), [2, 1]);
+    }
+
+    #[test]
+    fn matches_typos() {
+        let index = index(&["Buy milk", "Buy groceries", "Call mom"]);
+        assert_eq!(ids(&index.search("milke", 10)), [1]);
+        assert_eq!(ids(&index.search("grocries", 10)), [2]);
+        assert!(index.search("milkshake", 10).is_empty());
+    }
+
+    #[test]
+    fn ranks_by_coverage_then_frequency() {
+        let index = index(&[
+            "Buy milk",
+            "Buy bread and buy milk",
+            "Bread for the ducks",
+            "Bake bread",
+        ]);
+        // Only todo 2 has both words
+        assert_eq!(ids(&index.search("buy bread", 10))[0], 2);
+        // Todo 2 says it twice
+        assert_eq!(ids(&index.search("buy", 10)), [2, 1]);
+        // Ties go to the lowest id
+        assert_eq!(ids(&index.search("bread", 10)), [2, 3, 4]);
+        assert_eq!(ids(&index.search("bread", 2)), [2, 3]);
+    }
+
+    #[test]
+    fn match_offsets_are_byte_ranges() {
+        let note = "Café crème et crème brûlée";
+        let hits = index(&[note]).search("creme brûlée", 10);
+        assert_eq!(hits.len(), 1);
+        let matched: Vec<&str> = hits[0]
+            .matches
+            .iter()
+            .map(|range| &note[range.clone()])
+            .collect();
+        assert_eq!(matched, ["crème", "crème", "brûlée"]);
+    }
+
+    #[test]
+    fn keeps_up_with_changes() {
+        let mut index = index(&["Buy milk", "Walk the dog"]);
+        index.insert(ToDo::new("Buy oat milk", 2));
+        assert_eq!(ids(&index.search("milk", 10)), [1, 2]);
+        assert!(index.search("dog", 10).is_empty());
+
+        index.remove(1);
+        assert_eq!(ids(&index.search("milk", 10)), [2]);
+        assert_eq!(index.len(), 1);
+    }
+}
@@ -0,0 +1,365 @@
+mod memory_store;
+mod redis_store;
+
+pub use memory_store::MemoryStore;
+pub use redis_store::RedisStore;
+
+use crate::idempotency::Recorded;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::batch::{Batch, BatchOp, BatchResult};
+use common::history::{Change, Revision};
+use common::lists::{NewTaskList, TaskList, TaskListPatch};
+use common::query::{ListQuery, Page};
+use common::tags::TagCount;
+use common::validation::FieldError;
+use common::{ApiError, NewToDo, ToDo, ToDoPatch};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const UNABLE_TO_CONNECT: &str = "Unable to connect to Redis";
+pub const FAILED_TO_STORE_DATA: &str = "Failed to store data";
+pub const FAILED_TO_DELETE_DATA: &str = "Failed to delete data";
+pub const FAILED_TO_RETRIEVE_DATA: &str = "Failed to retireve data";
+
+pub type StoreResult<T> = Result<T, ApiError>;
+
+/// Storage backend behind the axum handlers, shared as router state. Every
+/// change to a todo is recorded as a `Revision` in its history
+#[async_trait]
+pub trait TodoStore: Send + Sync {
+    /// Stores a new `ToDo` under a freshly assigned id
+    async fn create(&self, new_todo: NewToDo) -> StoreResult<ToDo>;
+
+    async fn get(&self, id: usize) -> StoreResult<Option<ToDo>>;
+
+    /// One page of the todos matching `query`
+    async fn list(&self, query: &ListQuery) -> StoreResult<Page<ToDo>>;
+
+    /// Returns `None` when there is no `ToDo` with this id, a version conflict
+    /// when it does not have `patch.version`
+    async fn update(&self, id: usize, patch: ToDoPatch) -> StoreResult<Option<ToDo>>;
+
+    /// Moves the todo to the trash, returns `false` when there was nothing to
+    /// delete and a version conflict when it does not have `expected`. Only
+    /// `trash`, `restore` and `purge` see trashed todos
+    async fn delete(&self, id: usize, expected: Option<u64>) -> StoreResult<bool>;
+
+    /// Trashed todos, the most recently deleted first
+    async fn trash(&self) -> StoreResult<Vec<ToDo>>;
+
+    /// Takes the todo out of the trash, returns `None` when it is not in there
+    /// and a version conflict when it does not have `expected`
+    async fn restore(&self, id: usize, expected: Option<u64>) -> StoreResult<Option<ToDo>>;
+
+    /// Deletes for good, history included, the todos trashed before
+    /// `before`, every trashed todo without it. Returns their ids
+    async fn purge(&self, before: Option<DateTime<Utc>>) -> StoreResult<Vec<usize>>;
+
+    /// Kept revisions of todo `id`, trashed or not, the oldest first. Empty
+    /// when there is no such todo
+    async fn history(&self, id: usize) -> StoreResult<Vec<Revision>>;
+
+    /// Applies every operation of `batch` or, when one of them fails, none,
+    /// see `plan_batch`. Lists are not checked
+    async fn batch(&self, batch: Batch) -> StoreResult<BatchOutcome>;
+
+    /// Every tag in use with the number of todos carrying it, sorted by tag
+    async fn tags(&self) -> StoreResult<Vec<TagCount>>;
+
+    /// Tags `into` every todo tagged with one of `from` instead, returns the
+    /// todos changed. Tags must be normalized
+    async fn merge_tags(&self, from: &[String], into: &str) -> StoreResult<Vec<ToDo>>;
+
+    /// Stored lists in no particular order, the inbox is not one of them
+    async fn lists(&self) -> StoreResult<Vec<TaskList>>;
+
+    async fn get_list(&self, id: usize) -> StoreResult<Option<TaskList>>;
+
+    /// Stores a new `TaskList` under a freshly assigned id
+    async fn create_list(&self, new_list: NewTaskList) -> StoreResult<TaskList>;
+
+    /// Returns `None` when there is no `TaskList` with this id
+    async fn update_list(&self, id: usize, patch: TaskListPatch) -> StoreResult<Option<TaskList>>;
+
+    /// Moves the todos of the list to the inbox and deletes it, returns the
+    /// todos moved or `None` when there was nothing to delete
+    async fn delete_list(&self, id: usize) -> StoreResult<Option<Vec<ToDo>>>;
+}
+
+/// Storage of the responses recorded by the `idempotency` middleware, kept
+/// for a while under their `Idempotency-Key`
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Keeps `pending` under the `Idempotency-Key` `key` for `ttl` unless
+    /// the key is taken, in which case what is kept under it is returned
+    async fn claim_key(
+        &self,
+        key: &str,
+        pending: &Recorded,
+        ttl: Duration,
+    ) -> StoreResult<Option<Recorded>>;
+
+    /// Keeps `done` under a claimed `key` for `window`
+    async fn remember_key(&self, key: &str, done: &Recorded, window: Duration) -> StoreResult<()>;
+
+    /// Frees a claimed `key`
+    async fn release_key(&self, key: &str) -> StoreResult<()>;
+}
+
+/// What `TodoStore::batch` did
+pub struct BatchOutcome {
+    pub results: Vec<BatchResult>,
+    /// Todos updated or deleted as they were before the batch
+    pub before: BTreeMap<usize, ToDo>,
+}
+
+/// A batch applied to copies of the todos it touches, left for a store to
+/// write
+pub struct BatchPlan {
+    pub results: Vec<BatchResult>,
+    /// Todos touched as they were before, created ones are not in there
+    pub before: BTreeMap<usize, ToDo>,
+    /// Todos touched as they are after, trashed ones have `deleted_at` set
+    pub after: BTreeMap<usize, ToDo>,
+    /// Revisions to record, in order
+    pub revisions: Vec<(Change, ToDo)>,
+}
+
+/// Applies the operations of `batch` in order to `current`, the untrashed
+/// todos it targets, and numbers created todos from `first_id` on. Fails on
+/// the first operation that cannot be applied, naming it
+pub fn plan_batch(
+    batch: Batch,
+    current: BTreeMap<usize, ToDo>,
+    first_id: usize,
+) -> StoreResult<BatchPlan> {
+    let not_found = |index: usize, id: usize| {
+        ApiError::not_found(format!("Operation {}: ToDo {} not found", index, id))
+    };
+    let invalid = |index: usize, errors: Vec<FieldError>| {
+        ApiError::invalid(
+            errors
+                .into_iter()
+                .map(|error| FieldError {
+                    field: format!("ops[{}].{}", index, error.field),
+                    ..error
+                })
+                .collect(),
+        )
+    };
+
+    let mut after = current.clone();
+    let mut next_id = first_id;
+    let mut results = vec![];
+    let mut revisions = vec![];
+    for (index, op) in batch.ops.into_iter().enumerate() {
+        match op {
+            BatchOp::Create { todo } => {
+                todo.validate().map_err(|errors| invalid(index, errors))?;
+                let todo = todo.into_todo(next_id);
+                next_id += 1;
+                after.insert(todo.id, todo.clone());
+                revisions.push((Change::Created, todo.clone()));
+                results.push(BatchResult::Create { todo });
+            }
+            BatchOp::Update { id, patch } => {
+                let todo = after
+                    .get_mut(&id)
+                    .filter(|todo| todo.deleted_at.is_none())
+                    .ok_or_else(|| not_found(index, id))?;
+                todo.check_version(patch.version)?;
+                todo.validate_patch(&patch)
+                    .map_err(|errors| invalid(index, errors))?;
+                todo.apply(patch);
+                revisions.push((Change::Updated, todo.clone()));
+                results.push(BatchResult::Update { todo: todo.clone() });
+            }
+            BatchOp::Delete { id, version } => {
+                let todo = after
+                    .get_mut(&id)
+                    .filter(|todo| todo.deleted_at.is_none())
+                    .ok_or_else(|| not_found(index, id))?;
+                todo.check_version(version)?;
+                todo.set_deleted(true);
+                revisions.push((Change::Deleted, todo.clone()));
+                results.push(BatchResult::Delete { id });
+            }
+        }
+    }
+
+    Ok(BatchPlan {
+        results,
+        before: current,
+        after,
+        revisions,
+    })
+}
+
+/// Patch moving a todo to another list
+pub fn move_patch(list_id: usize) -> ToDoPatch {
+    ToDoPatch {
+        list_id: Some(list_id),
+        ..ToDoPatch::default()
+    }
+}
+
+/// Patch moving the tags of `todo` from `from` to `into`, `None` when it
+/// carries none of them
+pub fn retag_patch(todo: &ToDo, from: &[String], into: &str) -> Option<ToDoPatch> {
+    if !todo.tags.iter().any(|tag| from.contains(tag)) {
+        return None;
+    }
+    let mut tags: Vec<String> = todo
+        .tags
+        .iter()
+        .filter(|tag| !from.contains(tag))
+        .cloned()
+        .collect();
+    tags.push(into.to_string());
+
+    Some(ToDoPatch {
+        tags: Some(tags),
+        ..ToDoPatch::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_todo(todo_info: &str) -> NewToDo {
+        NewToDo {
+            todo_info: todo_info.to_string(),
+            ..NewToDo::default()
+        }
+    }
+
+    fn current() -> BTreeMap<usize, ToDo> {
+        [ToDo::new("Water the plants", 1), ToDo::new("Buy milk", 2)]
+            .into_iter()
+            .map(|todo| (todo.id, todo))
+            .collect()
+    }
+
+    fn plan(ops: Vec<BatchOp>) -> StoreResult<BatchPlan> {
+        plan_batch(Batch { ops }, current(), 10)
+    }
+
+    #[test]
+    fn applies_operations_in_order() {
+        let current = current();
+        let ops = vec![
+            BatchOp::Create {
+                todo: new_todo("Call mom"),
+            },
+            BatchOp::Update {
+                id: 1,
+                patch: ToDoPatch::completed(true),
+            },
+            BatchOp::Create {
+                todo: new_todo("Walk the dog"),
+            },
+            BatchOp::Delete {
+                id: 2,
+                version: Some(1),
+            },
+        ];
+        let plan = plan_batch(Batch { ops }, current.clone(), 10).unwrap();
+
+        let created: Vec<usize> = plan
+            .results
+            .iter()
+            .filter_map(|result| match result {
+                BatchResult::Create { todo } => Some(todo.id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(created, [10, 11]);
+        assert_eq!(plan.before, current);
+        assert!(plan.after[&1].completed);
+        assert_eq!(plan.after[&1].version, 2);
+        assert!(plan.after[&2].deleted_at.is_some());
+        assert_eq!(plan.after[&11].todo_info, "Walk the dog");
+
+        let changes: Vec<(Change, usize)> = plan
+            .revisions
+            .iter()
+            .map(|(change, todo)| (*change, todo.id))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (Change::Created, 10),
+                (Change::Updated, 1),
+                (Change::Created, 11),
+                (Change::Deleted, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_operations_see_earlier_ones() {
+        let plan = plan(vec![
+            BatchOp::Update {
+                id: 1,
+                patch: ToDoPatch::completed(true),
+            },
+            BatchOp::Update {
+                id: 1,
+                patch: ToDoPatch {
+                    version: Some(2),
+                    ..ToDoPatch::completed(false)
+                },
+            },
+        ])
+        .unwrap();
+        assert!(!plan.after[&1].completed);
+        assert_eq!(plan.after[&1].version, 3);
+
+        let deleted_then_updated = self::plan(vec![
+            BatchOp::Delete {
+                id: 1,
+                version: None,
+            },
+            BatchOp::Update {
+                id: 1,
+                patch: ToDoPatch::completed(true),
+            },
+        ]);
+        assert_eq!(
+            deleted_then_updated.err().map(|e| e.message().to_string()),
+            Some("Operation 1: ToDo 1 not found".to_string())
+        );
+    }
+
+    #[test]
+    fn fails_on_the_first_bad_operation() {
+        let missing = plan(vec![BatchOp::Delete {
+            id: 3,
+            version: None,
+        }]);
+        assert_eq!(missing.err().map(|e| e.status()), Some(404));
+
+        let stale = plan(vec![BatchOp::Update {
+            id: 2,
+            patch: ToDoPatch {
+                version: Some(4),
+                ..ToDoPatch::completed(true)
+            },
+        }]);
+        assert!(matches!(stale, Err(ApiError::VersionConflict { .. })));
+
+        let invalid = plan(vec![
+            BatchOp::Create {
+                todo: new_todo("Call mom"),
+            },
+            BatchOp::Create { todo: new_todo("") },
+        ]);
+        let Err(ApiError::Invalid { fields, .. }) = invalid else {
+            panic!("expected a validation error");
+        };
+        let fields: Vec<&str> = fields.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, ["ops[1].todo_info"]);
+    }
+}
@@ -0,0 +1,480 @@
+use super::{
+    BatchOutcome, IdempotencyStore, StoreResult, TodoStore, move_patch, plan_batch, retag_patch,
+};
+use crate::idempotency::Recorded;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::batch::Batch;
+use common::history::{Change, Revision};
+use common::lists::{INBOX_LIST_ID, NewTaskList, TaskList, TaskListPatch};
+use common::query::{ListQuery, Page};
+use common::tags::TagCount;
+use common::{NewToDo, ToDo, ToDoPatch};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps everything in process, data is lost when the server stops
+pub struct MemoryStore {
+    inner: Mutex<Inner>,
+    /// Revisions kept per todo
+    history_limit: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: usize,
+    todos: BTreeMap<usize, ToDo>,
+    /// Trashed todos, kept apart so that nothing else sees them
+    trash: BTreeMap<usize, ToDo>,
+    next_list_id: usize,
+    lists: BTreeMap<usize, TaskList>,
+    history: BTreeMap<usize, VecDeque<Revision>>,
+    /// Requests seen by `Idempotency-Key`, with when they expire
+    idempotency: HashMap<String, (Instant, Recorded)>,
+}
+
+impl Inner {
+    /// Appends a revision of `todo`, dropping the oldest ones past `limit`
+    fn record(&mut self, limit: usize, change: Change, todo: &ToDo) {
+        let history = self.history.entry(todo.id).or_default();
+        let rev = history.back().map_or(1, |last| last.rev + 1);
+        history.push_back(Revision {
+            rev,
+            at: Utc::now(),
+            change,
+            todo: todo.clone(),
+        });
+        while history.len() > limit {
+            history.pop_front();
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new(history_limit: usize) -> Self {
+        Self {
+            inner: Mutex::default(),
+            history_limit,
+        }
+    }
+}
+
+#[async_trait]
+impl TodoStore for MemoryStore {
+    async fn create(&self, new_todo: NewToDo) -> StoreResult<ToDo> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let todo = new_todo.into_todo(inner.next_id);
+        inner.todos.insert(todo.id, todo.clone());
+        inner.record(self.history_limit, Change::Created, &todo);
+
+        Ok(todo)
+    }
+
+    async fn get(&self, id: usize) -> StoreResult<Option<ToDo>> {
+        Ok(self.inner.lock().unwrap().todos.get(&id).cloned())
+    }
+
+    async fn list(&self, query: &ListQuery) -> StoreResult<Page<ToDo>> {
+        let todos = self.inner.lock().unwrap().todos.values().cloned().collect();
+
+        query.apply(todos)
+    }
+
+    async fn update(&self, id: usize, patch: ToDoPatch) -> StoreResult<Option<ToDo>> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(todo) = inner.todos.get_mut(&id) else {
+            return Ok(None);
+        };
+        todo.check_version(patch.version)?;
+        todo.apply(patch);
+        let todo = todo.clone();
+        inner.record(self.history_limit, Change::Updated, &todo);
+
+        Ok(Some(todo))
+    }
+
+    async fn delete(&self, id: usize, expected: Option<u64>) -> StoreResult<bool> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(todo) = inner.todos.get(&id) {
+            todo.check_version(expected)?;
+        }
+        let Some(mut todo) = inner.todos.remove(&id) else {
+            return Ok(false);
+        };
+        todo.set_deleted(true);
+        inner.record(self.history_limit, Change::Deleted, &todo);
+        inner.trash.insert(id, todo);
+
+        Ok(true)
+    }
+
+    async fn trash(&self) -> StoreResult<Vec<ToDo>> {
+        let mut todos: Vec<ToDo> = self.inner.lock().unwrap().trash.values().cloned().collect();
+        todos.sort_by_key(|todo| Reverse(todo.deleted_at));
+
+        Ok(todos)
+    }
+
+    async fn restore(&self, id: usize, expected: Option<u64>) -> StoreResult<Option<ToDo>> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(todo) = inner.trash.get(&id) {
+            todo.check_version(expected)?;
+        }
+        let Some(mut todo) = inner.trash.remove(&id) else {
+            return Ok(None);
+        };
+        todo.set_deleted(false);
+        inner.todos.insert(id, todo.clone());
+        inner.record(self.history_limit, Change::Restored, &todo);
+
+        Ok(Some(todo))
+    }
+
+    async fn purge(&self, before: Option<DateTime<Utc>>) -> StoreResult<Vec<usize>> {
+        let mut inner = self.inner.lock().unwrap();
+        let ids: Vec<usize> = inner
+            .trash
+            .values()
+            .filter(|todo| before.is_none_or(|before| todo.deleted_at < Some(before)))
+            .map(|todo| todo.id)
+            .collect();
+        for id in &ids {
+            inner.trash.remove(id);
+            inner.history.remove(id);
+        }
+
+        Ok(ids)
+    }
+
+    async fn history(&self, id: usize) -> StoreResult<Vec<Revision>> {
+        let inner = self.inner.lock().unwrap();
+
+        Ok(inner
+            .history
+            .get(&id)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn batch(&self, batch: Batch) -> StoreResult<BatchOutcome> {
+        let mut inner = self.inner.lock().unwrap();
+        let current = batch
+            .targets()
+            .iter()
+            .filter_map(|id| inner.todos.get(id))
+            .map(|todo| (todo.id, todo.clone()))
+            .collect();
+        let creates = batch.creates();
+        let plan = plan_batch(batch, current, inner.next_id + 1)?;
+
+        inner.next_id += creates;
+        for (id, todo) in plan.after {
+            if todo.deleted_at.is_some() {
+                inner.todos.remove(&id);
+                inner.trash.insert(id, todo);
+            } else {
+                inner.todos.insert(id, todo);
+            }
+        }
+        for (change, todo) in &plan.revisions {
+            inner.record(self.history_limit, *change, todo);
+        }
+
+        Ok(BatchOutcome {
+            results: plan.results,
+            before: plan.before,
+        })
+    }
+
+    async fn tags(&self) -> StoreResult<Vec<TagCount>> {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for todo in self.inner.lock().unwrap().todos.values() {
+            for tag in &todo.tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+
+        Ok(counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect())
+    }
+
+    async fn merge_tags(&self, from: &[String], into: &str) -> StoreResult<Vec<ToDo>> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut changed = vec![];
+        for todo in inner.todos.values_mut() {
+            if let Some(patch) = retag_patch(todo, from, into) {
+                todo.apply(patch);
+                changed.push(todo.clone());
+            }
+        }
+        for todo in &changed {
+            inner.record(self.history_limit, Change::Updated, todo);
+        }
+
+        Ok(changed)
+    }
+
+    async fn lists(&self) -> StoreResult<Vec<TaskList>> {
+        Ok(self.inner.lock().unwrap().lists.values().cloned().collect())
+    }
+
+    async fn get_list(&self, id: usize) -> StoreResult<Option<TaskList>> {
+        Ok(self.inner.lock().unwrap().lists.get(&id).cloned())
+    }
+
+    async fn create_list(&self, new_list: NewTaskList) -> StoreResult<TaskList> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_list_id += 1;
+        let list = new_list.into_list(inner.next_list_id);
+        inner.lists.insert(list.id, list.clone());
+
+        Ok(list)
+    }
+
+    async fn update_list(&self, id: usize, patch: TaskListPatch) -> StoreResult<Option<TaskList>> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(list) = inner.lists.get_mut(&id) else {
+            return Ok(None);
+        };
+        list.apply(patch);
+
+        Ok(Some(list.clone()))
+    }
+
+    async fn delete_list(&self, id: usize) -> StoreResult<Option<Vec<ToDo>>> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.lists.remove(&id).is_none() {
+            return Ok(None);
+        }
+        let mut moved = vec![];
+        for todo in inner.todos.values_mut().filter(|todo| todo.list_id == id) {
+            todo.apply(move_patch(INBOX_LIST_ID));
+            moved.push(todo.clone());
+        }
+        for todo in &moved {
+            inner.record(self.history_limit, Change::Updated, todo);
+        }
+
+        Ok(Some(moved))
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryStore {
+    async fn claim_key(
+        &self,
+        key: &str,
+        pending: &Recorded,
+        ttl: Duration,
+    ) -> StoreResult<Option<Recorded>> {
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        inner.idempotency.retain(|_, (expires, _)| *expires > now);
+        if let Some((_, recorded)) = inner.idempotency.get(key) {
+            return Ok(Some(recorded.clone()));
+        }
+        inner
+            .idempotency
+            .insert(key.to_string(), (now + ttl, pending.clone()));
+
+        Ok(None)
+    }
+
+    async fn remember_key(&self, key: &str, done: &Recorded, window: Duration) -> StoreResult<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .idempotency
+            .insert(key.to_string(), (Instant::now() + window, done.clone()));
+
+        Ok(())
+    }
+
+    async fn release_key(&self, key: &str) -> StoreResult<()> {
+        self.inner.lock().unwrap().idempotency.remove(key);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::ApiError;
+    use common::batch::BatchOp;
+
+    fn new_todo(todo_info: &str) -> NewToDo {
+        NewToDo {
+            todo_info: todo_info.to_string(),
+            ..NewToDo::default()
+        }
+    }
+
+    fn changes(history: &[Revision]) -> Vec<(u64, Change)> {
+        history
+            .iter()
+            .map(|revision| (revision.rev, revision.change))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn creates_and_gets_todos() {
+        let store = MemoryStore::new(10);
+        let milk = store.create(new_todo("Buy milk")).await.unwrap();
+        let dog = store.create(new_todo("Walk the dog")).await.unwrap();
+        assert_eq!((milk.id, dog.id), (1, 2));
+        assert_eq!(milk.version, 1);
+
+        assert_eq!(store.get(1).await.unwrap(), Some(milk));
+        assert_eq!(store.get(3).await.unwrap(), None);
+        let page = store.list(&ListQuery::default()).await.unwrap();
+        assert_eq!(page.items.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn updates_todos_at_their_version() {
+        let store = MemoryStore::new(10);
+        store.create(new_todo("Buy milk")).await.unwrap();
+
+        let patch = ToDoPatch {
+            version: Some(1),
+            ..ToDoPatch::completed(true)
+        };
+        let todo = store.update(1, patch.clone()).await.unwrap().unwrap();
+        assert!(todo.completed);
+        assert_eq!(todo.version, 2);
+        assert_eq!(store.get(1).await.unwrap(), Some(todo));
+
+        // The same patch is now one version behind
+        let stale = store.update(1, patch).await;
+        assert!(matches!(stale, Err(ApiError::VersionConflict { .. })));
+        assert_eq!(
+            store.update(2, ToDoPatch::completed(true)).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn trashes_and_restores_todos() {
+        let store = MemoryStore::new(10);
+        for todo_info in ["Buy milk", "Walk the dog"] {
+            store.create(new_todo(todo_info)).await.unwrap();
+        }
+
+        assert!(matches!(
+            store.delete(1, Some(2)).await,
+            Err(ApiError::VersionConflict { .. })
+        ));
+        assert!(store.delete(1, Some(1)).await.unwrap());
+        assert!(store.delete(2, None).await.unwrap());
+        assert!(!store.delete(1, None).await.unwrap());
+        assert_eq!(store.get(1).await.unwrap(), None);
+        assert!(
+            store
+                .list(&ListQuery::default())
+                .await
+                .unwrap()
+                .items
+                .is_empty()
+        );
+
+        // The most recently deleted first
+        let trash = store.trash().await.unwrap();
+        let ids: Vec<usize> = trash.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, [2, 1]);
+        assert!(trash.iter().all(|todo| todo.deleted_at.is_some()));
+
+        assert!(matches!(
+            store.restore(1, Some(1)).await,
+            Err(ApiError::VersionConflict { .. })
+        ));
+        let restored = store.restore(1, Some(2)).await.unwrap().unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(restored.version, 3);
+        assert_eq!(store.get(1).await.unwrap(), Some(restored));
+        assert_eq!(store.restore(1, None).await.unwrap(), None);
+
+        assert_eq!(store.purge(None).await.unwrap(), [2]);
+        assert!(store.trash().await.unwrap().is_empty());
+        assert!(store.history(2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_history() {
+        let store = MemoryStore::new(3);
+        store.create(new_todo("Buy milk")).await.unwrap();
+        store.update(1, ToDoPatch::completed(true)).await.unwrap();
+        store.delete(1, None).await.unwrap();
+        assert_eq!(
+            changes(&store.history(1).await.unwrap()),
+            [
+                (1, Change::Created),
+                (2, Change::Updated),
+                (3, Change::Deleted)
+            ]
+        );
+
+        // Only the last revisions are kept, numbered on
+        store.restore(1, None).await.unwrap();
+        assert_eq!(
+            changes(&store.history(1).await.unwrap()),
+            [
+                (2, Change::Updated),
+                (3, Change::Deleted),
+                (4, Change::Restored)
+            ]
+        );
+        assert!(store.history(2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn applies_batches_entirely_or_not_at_all() {
+        let store = MemoryStore::new(10);
+        store.create(new_todo("Buy milk")).await.unwrap();
+
+        let failing = Batch {
+            ops: vec![
+                BatchOp::Create {
+                    todo: new_todo("Walk the dog"),
+                },
+                BatchOp::Delete {
+                    id: 5,
+                    version: None,
+                },
+            ],
+        };
+        assert_eq!(
+            store.batch(failing).await.err().map(|e| e.status()),
+            Some(404)
+        );
+        assert_eq!(
+            store.list(&ListQuery::default()).await.unwrap().items.len(),
+            1
+        );
+
+        let batch = Batch {
+            ops: vec![
+                BatchOp::Create {
+                    todo: new_todo("Walk the dog"),
+                },
+                BatchOp::Delete {
+                    id: 1,
+                    version: None,
+                },
+            ],
+        };
+        let outcome = store.batch(batch).await.unwrap();
+        assert_eq!(outcome.results.len(), 2);
+        assert_eq!(outcome.before.keys().collect::<Vec<_>>(), [&1]);
+        assert_eq!(
+            store.get(2).await.unwrap().map(|todo| todo.todo_info),
+            Some("Walk the dog".to_string())
+        );
+        assert_eq!(store.trash().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,1225 @@
+use super::{
+    BatchOutcome, FAILED_TO_DELETE_DATA, FAILED_TO_RETRIEVE_DATA, FAILED_TO_STORE_DATA,
+    IdempotencyStore, StoreResult, TodoStore, UNABLE_TO_CONNECT, move_patch, plan_batch,
+    retag_patch,
+};
+use crate::idempotency::Recorded;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::batch::Batch;
+use common::history::{Change, Revision};
+use common::lists::{INBOX_LIST_ID, NewTaskList, TaskList, TaskListPatch};
+use common::query::{ListQuery, Page};
+use common::schema::{SCHEMA_VERSION, version_of};
+use common::tags::TagCount;
+use common::{ApiError, NewToDo, ToDo, ToDoPatch};
+use deadpool_redis::{Connection, Pool, redis};
+use log::{error, info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+/// Counter used to hand out ids
+const NEXT_ID_KEY: &str = "todos:next_id";
+/// Sorted set of every stored id, scored by the id itself. Trashed todos are
+/// not in there
+const INDEX_KEY: &str = "todos:index";
+/// Sorted set of the trashed ids, scored by `deleted_at` in milliseconds.
+/// Trashed todos are in no tag or list set either
+const TRASH_KEY: &str = "todos:trash";
+/// Set of every tag in use, entries whose tag set is empty are dropped lazily
+const TAGS_KEY: &str = "todos:tags";
+/// Hash of every `TaskList` by id
+const LISTS_KEY: &str = "todos:lists";
+/// Counter used to hand out list ids
+const NEXT_LIST_ID_KEY: &str = "todos:lists:next_id";
+/// Counter used before todos were namespaced
+const LEGACY_NEXT_ID_KEY: &str = "next_todo_id";
+/// Ids read at once by the schema migration
+const MIGRATION_BATCH: usize = 100;
+/// Times a batch is tried before giving up on todos changing under it
+const BATCH_ATTEMPTS: usize = 3;
+const BATCH_CONFLICT: &str = "The todos changed during the batch, try again";
+/// Times a todo is read and saved again before giving up on it changing
+const SAVE_ATTEMPTS: usize = 3;
+const SAVE_CONFLICT: &str = "A ToDo kept changing while it was updated, try again";
+/// Times the todos of a list are moved before giving up on new ones
+const LIST_ATTEMPTS: usize = 3;
+const LIST_CONFLICT: &str = "Todos kept being added to the list, try again";
+
+fn todo_key(id: usize) -> String {
+    format!("todo:{}", id)
+}
+
+/// Sorted set of the kept revisions of todo `id`, scored by their number
+fn history_key(id: usize) -> String {
+    format!("todo:{}:history", id)
+}
+
+/// Counter used to number the revisions of todo `id`
+fn rev_key(id: usize) -> String {
+    format!("todo:{}:rev", id)
+}
+
+/// Request seen with `Idempotency-Key` `key`, expiring with its window
+fn idempotency_key(key: &str) -> String {
+    format!("todos:idempotency:{}", key)
+}
+
+/// Set of the ids tagged with `tag`
+fn tag_key(tag: &str) -> String {
+    format!("todos:tag:{}", tag)
+}
+
+/// Set of the ids filed in list `id`, the inbox has none
+fn list_key(id: usize) -> String {
+    format!("todos:list:{}", id)
+}
+
+/// Queues the tag and list set updates of todo `id` going from `old` to `new`,
+/// `None` when it does not exist before or after
+fn index_todo(pipe: &mut redis::Pipeline, id: usize, old: Option<&ToDo>, new: Option<&ToDo>) {
+    let old_tags = old.map_or(&[][..], |todo| &todo.tags);
+    let new_tags = new.map_or(&[][..], |todo| &todo.tags);
+    for tag in old_tags.iter().filter(|tag| !new_tags.contains(tag)) {
+        pipe.cmd("SREM").arg(tag_key(tag)).arg(id).ignore();
+    }
+    for tag in new_tags.iter().filter(|tag| !old_tags.contains(tag)) {
+        pipe.cmd("SADD").arg(tag_key(tag)).arg(id).ignore();
+        pipe.cmd("SADD").arg(TAGS_KEY).arg(tag).ignore();
+    }
+
+    let filed = |todo: &ToDo| (todo.list_id != INBOX_LIST_ID).then_some(todo.list_id);
+    let old_list = old.and_then(filed);
+    let new_list = new.and_then(filed);
+    if old_list != new_list {
+        if let Some(list) = old_list {
+            pipe.cmd("SREM").arg(list_key(list)).arg(id).ignore();
+        }
+        if let Some(list) = new_list {
+            pipe.cmd("SADD").arg(list_key(list)).arg(id).ignore();
+        }
+    }
+}
+
+/// Whether a stored todo has an older schema version than the current one
+fn is_outdated(todo_str: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(todo_str)
+        .ok()
+        .and_then(|value| value.as_object().map(version_of))
+        .is_some_and(|version| version < SCHEMA_VERSION)
+}
+
+fn redis_error(msg: &'static str) -> impl Fn(redis::RedisError) -> ApiError {
+    move |e| {
+        error!("{}: {}", msg, e);
+        ApiError::internal(msg)
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisStore {
+    pool: Pool,
+    /// Revisions kept per todo
+    history_limit: usize,
+}
+
+impl RedisStore {
+    pub fn new(pool: Pool, history_limit: usize) -> Self {
+        Self {
+            pool,
+            history_limit,
+        }
+    }
+
+    async fn conn(&self) -> StoreResult<Connection> {
+        self.pool.get().await.map_err(|e| {
+            error!("{}: {}", UNABLE_TO_CONNECT, e);
+            ApiError::unavailable(UNABLE_TO_CONNECT)
+        })
+    }
+
+    /// Reads todo `id` whether it is trashed or not
+    async fn read(&self, id: usize) -> StoreResult<Option<ToDo>> {
+        let mut conn = self.conn().await?;
+
+        Self::read_on(&mut conn, id).await
+    }
+
+    /// Watches the key of todo `id` and reads it, the next transaction on
+    /// `conn` is aborted when it changes in the meantime. See `unwatch`
+    async fn watch(conn: &mut Connection, id: usize) -> StoreResult<Option<ToDo>> {
+        let _: () = redis::cmd("WATCH")
+            .arg(todo_key(id))
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        Self::read_on(conn, id).await
+    }
+
+    /// Drops the watches of `conn` when no transaction follows them
+    async fn unwatch(conn: &mut Connection) -> StoreResult<()> {
+        redis::cmd("UNWATCH")
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))
+    }
+
+    /// Conflict carrying the live copy of todo `id` after a transaction on it
+    /// was aborted, `None` once it is gone
+    async fn conflict(conn: &mut Connection, id: usize) -> StoreResult<Option<ApiError>> {
+        let todo = Self::read_on(conn, id).await?;
+
+        Ok(todo
+            .filter(|todo| todo.deleted_at.is_none())
+            .map(ApiError::version_conflict))
+    }
+
+    /// `read` on a connection of the caller
+    async fn read_on(conn: &mut Connection, id: usize) -> StoreResult<Option<ToDo>> {
+        let todo_str: Option<String> = redis::cmd("GET")
+            .arg(todo_key(id))
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        todo_str
+            .map(|todo_str| ToDo::from_json(&todo_str))
+            .transpose()
+            .map_err(|e| {
+                error!("{}: {}", common::UNABLE_TO_PARSE_DATA, e);
+                ApiError::internal(common::UNABLE_TO_PARSE_DATA)
+            })
+    }
+
+    /// Reads the todos with these ids, entries that are missing or malformed
+    /// are logged and skipped
+    async fn fetch(conn: &mut Connection, ids: &[usize]) -> StoreResult<Vec<ToDo>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let keys: Vec<String> = ids.iter().map(|id| todo_key(*id)).collect();
+        let values: Vec<Option<String>> = redis::cmd("MGET")
+            .arg(&keys)
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        let todos = keys
+            .iter()
+            .zip(values)
+            .filter_map(|(key, value)| {
+                let Some(todo_str) = value else {
+                    warn!("Skipping {}, it is indexed but missing", key);
+                    return None;
+                };
+                ToDo::from_json(&todo_str)
+                    .map_err(|e| warn!("Skipping malformed {}: {}", key, e))
+                    .ok()
+            })
+            .collect();
+
+        Ok(todos)
+    }
+
+    /// Appends a revision of `todo` to its history, dropping the oldest ones
+    /// past the limit
+    async fn record(&self, conn: &mut Connection, change: Change, todo: &ToDo) -> StoreResult<()> {
+        let rev: u64 = redis::cmd("INCR")
+            .arg(rev_key(todo.id))
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        let revision = Revision {
+            rev,
+            at: Utc::now(),
+            change,
+            todo: todo.clone(),
+        };
+        let json = revision
+            .to_json()
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("ZADD")
+            .arg(history_key(todo.id))
+            .arg(rev)
+            .arg(&json)
+            .ignore()
+            .cmd("ZREMRANGEBYRANK")
+            .arg(history_key(todo.id))
+            .arg(0)
+            .arg(-(self.history_limit as i64) - 1)
+            .ignore()
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+
+        Ok(())
+    }
+
+    /// Overwrites todo `old` with `todo` and records it, unless it changed
+    /// since `old` was read. Returns `false` when it was deleted in the
+    /// meantime, a version conflict carrying the stored copy when it changed
+    async fn save(&self, conn: &mut Connection, old: &ToDo, todo: &ToDo) -> StoreResult<bool> {
+        let json = todo
+            .to_json()
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let stored = Self::watch(conn, todo.id).await?;
+        let Some(stored) = stored.filter(|stored| stored.deleted_at.is_none()) else {
+            Self::unwatch(conn).await?;
+            return Ok(false);
+        };
+        if stored.version != old.version {
+            Self::unwatch(conn).await?;
+            return Err(ApiError::version_conflict(stored));
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("SET")
+            .arg(todo_key(todo.id))
+            .arg(&json)
+            .ignore();
+        index_todo(&mut pipe, todo.id, Some(old), Some(todo));
+        // Nil when the transaction was aborted by the WATCH
+        let saved: Option<Vec<redis::Value>> = pipe
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        if saved.is_none() {
+            return match Self::conflict(conn, todo.id).await? {
+                Some(conflict) => Err(conflict),
+                None => Ok(false),
+            };
+        }
+        self.record(conn, Change::Updated, todo).await?;
+
+        Ok(true)
+    }
+
+    /// Saves `todo` with the patch `change` makes for it, reading it again
+    /// when it changed in the meantime. Returns `None` when it was deleted or
+    /// needs no change
+    async fn save_retrying(
+        &self,
+        conn: &mut Connection,
+        mut todo: ToDo,
+        change: impl Fn(&ToDo) -> Option<ToDoPatch>,
+    ) -> StoreResult<Option<ToDo>> {
+        for _ in 0..SAVE_ATTEMPTS {
+            let Some(patch) = change(&todo) else {
+                return Ok(None);
+            };
+            let mut new = todo.clone();
+            new.apply(patch);
+            match self.save(conn, &todo, &new).await {
+                Ok(saved) => return Ok(saved.then_some(new)),
+                Err(ApiError::VersionConflict { current, .. }) => todo = *current,
+                Err(e) => return Err(e),
+            }
+        }
+
+        warn!(
+            "{}: ToDo {} after {} attempts",
+            SAVE_CONFLICT, todo.id, SAVE_ATTEMPTS
+        );
+        Err(ApiError::conflict(SAVE_CONFLICT))
+    }
+
+    /// Moves todos stored under bare numeric keys by older versions into the
+    /// `todo:{id}` keyspace, returns how many were moved
+    pub async fn import_legacy_keys(&self) -> StoreResult<usize> {
+        let mut conn = self.conn().await?;
+
+        let mut cursor: u64 = 0;
+        let mut imported = 0;
+        let mut max_id = 0;
+        loop {
+            let (new_cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .cursor_arg(cursor)
+                .arg("MATCH")
+                .arg("[0-9]*")
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+            for key in keys {
+                let Ok(id) = key.parse::<usize>() else {
+                    continue;
+                };
+                let todo_str: Option<String> = redis::cmd("GET")
+                    .arg(&key)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+                let Some(todo) = todo_str.and_then(|s| ToDo::from_json(&s).ok()) else {
+                    warn!("Skipping legacy key {}, it does not hold a ToDo", key);
+                    continue;
+                };
+                if todo.id != id {
+                    warn!("Skipping legacy key {}, it holds ToDo {}", key, todo.id);
+                    continue;
+                }
+
+                let moved: bool = redis::cmd("RENAMENX")
+                    .arg(&key)
+                    .arg(todo_key(id))
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+                if !moved {
+                    warn!(
+                        "Skipping legacy key {}, {} already exists",
+                        key,
+                        todo_key(id)
+                    );
+                    continue;
+                }
+                let _: () = redis::cmd("ZADD")
+                    .arg(INDEX_KEY)
+                    .arg(id)
+                    .arg(id)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+                max_id = max_id.max(id);
+                imported += 1;
+            }
+            cursor = new_cursor;
+
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        let legacy_next_id: Option<usize> = redis::cmd("GET")
+            .arg(LEGACY_NEXT_ID_KEY)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        max_id = max_id.max(legacy_next_id.unwrap_or(0));
+        if max_id > 0 {
+            // Never hand out an id that was already used before the import
+            let next_id: Option<usize> = redis::cmd("GET")
+                .arg(NEXT_ID_KEY)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            if next_id.unwrap_or(0) < max_id {
+                let _: () = redis::cmd("SET")
+                    .arg(NEXT_ID_KEY)
+                    .arg(max_id)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+            }
+        }
+
+        if imported > 0 {
+            info!("Imported {} legacy ToDo(s)", imported);
+        }
+        Ok(imported)
+    }
+
+    /// Rewrites every todo stored with an older schema version in the current
+    /// one, trashed ones included, returns how many were rewritten. Reads
+    /// upgrade on their own, this only saves doing it on every read
+    pub async fn migrate_records(&self) -> StoreResult<usize> {
+        let mut conn = self.conn().await?;
+
+        let mut migrated = 0;
+        for set in [INDEX_KEY, TRASH_KEY] {
+            // ZSCAN may return an id twice, migrating it again is a no-op
+            let mut cursor = 0;
+            loop {
+                let (next, entries): (u64, Vec<String>) = redis::cmd("ZSCAN")
+                    .arg(set)
+                    .arg(cursor)
+                    .arg("COUNT")
+                    .arg(MIGRATION_BATCH)
+                    .query_async(&mut conn)
+                    .await
+                    .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+                // Members and scores alternate
+                for id in entries.iter().step_by(2) {
+                    let Ok(id) = id.parse() else {
+                        warn!("Skipping malformed id `{}` in {}", id, set);
+                        continue;
+                    };
+                    if Self::migrate_record(&mut conn, id).await? {
+                        migrated += 1;
+                    }
+                }
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+        }
+
+        info!(
+            "Migrated {} ToDo(s) to schema version {}",
+            migrated, SCHEMA_VERSION
+        );
+        Ok(migrated)
+    }
+
+    /// Rewrites todo `id` when it has an older schema version, returns `false`
+    /// when it did not need it or changed in the meantime
+    async fn migrate_record(conn: &mut Connection, id: usize) -> StoreResult<bool> {
+        let key = todo_key(id);
+
+        // WATCH so an update made in the meantime is not overwritten
+        let _: () = redis::cmd("WATCH")
+            .arg(&key)
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        let todo_str: Option<String> = redis::cmd("GET")
+            .arg(&key)
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        let json = match todo_str.filter(|todo_str| is_outdated(todo_str)) {
+            Some(todo_str) => ToDo::from_json(&todo_str)
+                .and_then(|todo| todo.to_json())
+                .map_err(|e| warn!("Cannot migrate {}: {}", key, e))
+                .ok(),
+            None => None,
+        };
+        let Some(json) = json else {
+            Self::unwatch(conn).await?;
+            return Ok(false);
+        };
+
+        // Nil when the transaction was aborted by the WATCH
+        let stored: Option<(redis::Value,)> = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(&json)
+            .arg("XX")
+            .query_async(conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+
+        Ok(stored.is_some_and(|(reply,)| reply != redis::Value::Nil))
+    }
+}
+
+#[async_trait]
+impl TodoStore for RedisStore {
+    async fn create(&self, new_todo: NewToDo) -> StoreResult<ToDo> {
+        let mut conn = self.conn().await?;
+
+        let id: usize = redis::cmd("INCR")
+            .arg(NEXT_ID_KEY)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        let todo = new_todo.into_todo(id);
+        let json = todo
+            .to_json()
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        // WATCH so a stale counter can never overwrite an existing ToDo, nor
+        // index one under the id
+        let (_, taken): ((), bool) = redis::pipe()
+            .cmd("WATCH")
+            .arg(todo_key(id))
+            .cmd("EXISTS")
+            .arg(todo_key(id))
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        let stored = if taken {
+            Self::unwatch(&mut conn).await?;
+            false
+        } else {
+            let mut pipe = redis::pipe();
+            pipe.atomic()
+                .cmd("SET")
+                .arg(todo_key(id))
+                .arg(&json)
+                .ignore()
+                .cmd("ZADD")
+                .arg(INDEX_KEY)
+                .arg(id)
+                .arg(id)
+                .ignore();
+            index_todo(&mut pipe, id, None, Some(&todo));
+            // Nil when the transaction was aborted by the WATCH
+            let stored: Option<()> = pipe
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+            stored.is_some()
+        };
+        if !stored {
+            error!("{}: id {} already in use", FAILED_TO_STORE_DATA, id);
+            return Err(ApiError::conflict(FAILED_TO_STORE_DATA));
+        }
+        self.record(&mut conn, Change::Created, &todo).await?;
+
+        Ok(todo)
+    }
+
+    async fn get(&self, id: usize) -> StoreResult<Option<ToDo>> {
+        let todo = self.read(id).await?;
+
+        Ok(todo.filter(|todo| todo.deleted_at.is_none()))
+    }
+
+    async fn list(&self, query: &ListQuery) -> StoreResult<Page<ToDo>> {
+        if !query.is_default_order() {
+            // Filters and other orders need every candidate, tag and list
+            // filters narrow them down to the members of their sets
+            let mut conn = self.conn().await?;
+            let mut sets: Vec<String> = query.tags().iter().map(|tag| tag_key(tag)).collect();
+            if let Some(list) = query.list.filter(|list| *list != INBOX_LIST_ID) {
+                sets.push(list_key(list));
+            }
+            let cmd = if sets.is_empty() {
+                let mut cmd = redis::cmd("ZRANGE");
+                cmd.arg(INDEX_KEY).arg(0).arg(-1);
+                cmd
+            } else {
+                let mut cmd = redis::cmd("SINTER");
+                cmd.arg(sets);
+                cmd
+            };
+            let ids: Vec<usize> = cmd
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            let todos = Self::fetch(&mut conn, &ids).await?;
+            return query.apply(todos);
+        }
+
+        let min = match query.decode_cursor()? {
+            Some(after) => format!("({}", after.id),
+            None => "-inf".to_string(),
+        };
+        let limit = query.limit();
+        let mut conn = self.conn().await?;
+
+        let ids: Vec<usize> = redis::cmd("ZRANGEBYSCORE")
+            .arg(INDEX_KEY)
+            .arg(min)
+            .arg("+inf")
+            .arg("LIMIT")
+            .arg(0)
+            .arg(limit + 1)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        // The cursor comes from the index so skipped entries do not end the listing
+        let next_cursor = (ids.len() > limit).then(|| ids[limit - 1].to_string());
+        let items = Self::fetch(&mut conn, &ids[..ids.len().min(limit)]).await?;
+
+        Ok(Page { items, next_cursor })
+    }
+
+    async fn update(&self, id: usize, patch: ToDoPatch) -> StoreResult<Option<ToDo>> {
+        let Some(mut todo) = self.get(id).await? else {
+            return Ok(None);
+        };
+        todo.check_version(patch.version)?;
+        let old = todo.clone();
+        todo.apply(patch);
+
+        let mut conn = self.conn().await?;
+        let stored = self.save(&mut conn, &old, &todo).await?;
+
+        Ok(stored.then_some(todo))
+    }
+
+    async fn delete(&self, id: usize, expected: Option<u64>) -> StoreResult<bool> {
+        let mut conn = self.conn().await?;
+
+        let old = Self::watch(&mut conn, id).await?;
+        let Some(old) = old.filter(|old| old.deleted_at.is_none()) else {
+            Self::unwatch(&mut conn).await?;
+            return Ok(false);
+        };
+        if let Err(e) = old.check_version(expected) {
+            Self::unwatch(&mut conn).await?;
+            return Err(e);
+        }
+        let mut todo = old.clone();
+        todo.set_deleted(true);
+        let deleted_at = todo.deleted_at.unwrap_or_else(Utc::now);
+        let json = todo
+            .to_json()
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("SET")
+            .arg(todo_key(id))
+            .arg(&json)
+            .arg("XX")
+            .cmd("ZREM")
+            .arg(INDEX_KEY)
+            .arg(id)
+            .ignore()
+            .cmd("ZADD")
+            .arg(TRASH_KEY)
+            .arg(deleted_at.timestamp_millis())
+            .arg(id)
+            .ignore();
+        index_todo(&mut pipe, id, Some(&old), None);
+        // Nil when the transaction was aborted by the WATCH
+        let trashed: Option<(bool,)> = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_DELETE_DATA))?;
+        let Some((trashed,)) = trashed else {
+            return match Self::conflict(&mut conn, id).await? {
+                Some(conflict) => Err(conflict),
+                None => Ok(false),
+            };
+        };
+        if trashed {
+            self.record(&mut conn, Change::Deleted, &todo).await?;
+        }
+
+        info!("Moved ToDo {} to the trash", id);
+
+        Ok(trashed)
+    }
+
+    async fn trash(&self) -> StoreResult<Vec<ToDo>> {
+        let mut conn = self.conn().await?;
+
+        let ids: Vec<usize> = redis::cmd("ZREVRANGE")
+            .arg(TRASH_KEY)
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        Self::fetch(&mut conn, &ids).await
+    }
+
+    async fn restore(&self, id: usize, expected: Option<u64>) -> StoreResult<Option<ToDo>> {
+        let mut conn = self.conn().await?;
+
+        // WATCH so that a purge in the meantime leaves no index entries behind
+        let old = Self::watch(&mut conn, id).await?;
+        let Some(mut todo) = old.filter(|todo| todo.deleted_at.is_some()) else {
+            Self::unwatch(&mut conn).await?;
+            return Ok(None);
+        };
+        if let Err(e) = todo.check_version(expected) {
+            Self::unwatch(&mut conn).await?;
+            return Err(e);
+        }
+        todo.set_deleted(false);
+        let json = todo
+            .to_json()
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("SET")
+            .arg(todo_key(id))
+            .arg(&json)
+            .arg("XX")
+            .cmd("ZREM")
+            .arg(TRASH_KEY)
+            .arg(id)
+            .ignore()
+            .cmd("ZADD")
+            .arg(INDEX_KEY)
+            .arg(id)
+            .arg(id)
+            .ignore();
+        index_todo(&mut pipe, id, None, Some(&todo));
+        // Nil when the transaction was aborted by the WATCH
+        let restored: Option<(bool,)> = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        let Some((restored,)) = restored else {
+            let current = Self::read_on(&mut conn, id).await?;
+            return match current.filter(|todo| todo.deleted_at.is_some()) {
+                Some(current) => Err(ApiError::version_conflict(current)),
+                None => Ok(None),
+            };
+        };
+        if restored {
+            self.record(&mut conn, Change::Restored, &todo).await?;
+        }
+
+        Ok(restored.then_some(todo))
+    }
+
+    async fn purge(&self, before: Option<DateTime<Utc>>) -> StoreResult<Vec<usize>> {
+        let mut conn = self.conn().await?;
+
+        let max = match before {
+            Some(before) => format!("({}", before.timestamp_millis()),
+            None => "+inf".to_string(),
+        };
+        let ids: Vec<usize> = redis::cmd("ZRANGEBYSCORE")
+            .arg(TRASH_KEY)
+            .arg("-inf")
+            .arg(max)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        let mut purged = vec![];
+        for id in ids {
+            // Only what is still in the trash, it may have been restored since
+            let removed: bool = redis::cmd("ZREM")
+                .arg(TRASH_KEY)
+                .arg(id)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_DELETE_DATA))?;
+            if !removed {
+                continue;
+            }
+            let _: () = redis::cmd("DEL")
+                .arg(todo_key(id))
+                .arg(history_key(id))
+                .arg(rev_key(id))
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_DELETE_DATA))?;
+            purged.push(id);
+        }
+
+        Ok(purged)
+    }
+
+    async fn history(&self, id: usize) -> StoreResult<Vec<Revision>> {
+        let mut conn = self.conn().await?;
+
+        let values: Vec<String> = redis::cmd("ZRANGE")
+            .arg(history_key(id))
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        Ok(values
+            .iter()
+            .filter_map(|value| {
+                Revision::from_json(value)
+                    .map_err(|e| warn!("Skipping malformed revision of {}: {}", id, e))
+                    .ok()
+            })
+            .collect())
+    }
+
+    async fn batch(&self, batch: Batch) -> StoreResult<BatchOutcome> {
+        if batch.ops.is_empty() {
+            return Ok(BatchOutcome {
+                results: vec![],
+                before: BTreeMap::new(),
+            });
+        }
+        let mut conn = self.conn().await?;
+
+        let creates = batch.creates();
+        let first_id = if creates > 0 {
+            let last_id: usize = redis::cmd("INCRBY")
+                .arg(NEXT_ID_KEY)
+                .arg(creates)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+            last_id + 1 - creates
+        } else {
+            0
+        };
+        let targets = batch.targets();
+        let keys: Vec<String> = targets
+            .iter()
+            .copied()
+            .chain(first_id..first_id + creates)
+            .map(todo_key)
+            .collect();
+
+        for _ in 0..BATCH_ATTEMPTS {
+            // WATCH so that the batch is aborted when one of its todos changes
+            // in the meantime
+            let _: () = redis::cmd("WATCH")
+                .arg(&keys)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            let values: Vec<Option<String>> = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            let (values, taken) = values.split_at(targets.len());
+            let current: BTreeMap<usize, ToDo> = values
+                .iter()
+                .flatten()
+                .filter_map(|todo_str| ToDo::from_json(todo_str).ok())
+                .filter(|todo| todo.deleted_at.is_none())
+                .map(|todo| (todo.id, todo))
+                .collect();
+
+            // A stale counter must never hand out an id in use
+            let planned = if taken.iter().any(Option::is_some) {
+                error!(
+                    "{}: ids from {} already in use",
+                    FAILED_TO_STORE_DATA, first_id
+                );
+                Err(ApiError::conflict(FAILED_TO_STORE_DATA))
+            } else {
+                plan_batch(batch.clone(), current, first_id)
+            };
+            let plan = match planned {
+                Ok(plan) => plan,
+                Err(e) => {
+                    Self::unwatch(&mut conn).await?;
+                    return Err(e);
+                }
+            };
+
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for (id, todo) in &plan.after {
+                let json = todo
+                    .to_json()
+                    .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+                let old = plan.before.get(id);
+                pipe.cmd("SET").arg(todo_key(*id)).arg(&json).ignore();
+                match todo.deleted_at {
+                    Some(deleted_at) => {
+                        pipe.cmd("ZREM")
+                            .arg(INDEX_KEY)
+                            .arg(id)
+                            .ignore()
+                            .cmd("ZADD")
+                            .arg(TRASH_KEY)
+                            .arg(deleted_at.timestamp_millis())
+                            .arg(id)
+                            .ignore();
+                    }
+                    None if old.is_none() => {
+                        pipe.cmd("ZADD").arg(INDEX_KEY).arg(id).arg(id).ignore();
+                    }
+                    None => {}
+                }
+                let new = todo.deleted_at.is_none().then_some(todo);
+                index_todo(&mut pipe, *id, old, new);
+            }
+            // Nil when the transaction was aborted by the WATCH
+            let stored: Option<Vec<redis::Value>> = pipe
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+            if stored.is_none() {
+                continue;
+            }
+
+            for (change, todo) in &plan.revisions {
+                self.record(&mut conn, *change, todo).await?;
+            }
+
+            return Ok(BatchOutcome {
+                results: plan.results,
+                before: plan.before,
+            });
+        }
+
+        warn!(
+            "{}: gave up after {} attempts",
+            BATCH_CONFLICT, BATCH_ATTEMPTS
+        );
+        Err(ApiError::conflict(BATCH_CONFLICT))
+    }
+
+    async fn tags(&self) -> StoreResult<Vec<TagCount>> {
+        let mut conn = self.conn().await?;
+
+        let mut tags: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(TAGS_KEY)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        tags.sort();
+        let mut pipe = redis::pipe();
+        for tag in &tags {
+            pipe.cmd("SCARD").arg(tag_key(tag));
+        }
+        let counts: Vec<usize> = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        let (used, unused): (Vec<TagCount>, Vec<TagCount>) = tags
+            .into_iter()
+            .zip(counts)
+            .map(|(tag, count)| TagCount { tag, count })
+            .partition(|tag| tag.count > 0);
+        if !unused.is_empty() {
+            let _: () = redis::cmd("SREM")
+                .arg(TAGS_KEY)
+                .arg(unused.iter().map(|tag| &tag.tag).collect::<Vec<_>>())
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_DELETE_DATA))?;
+        }
+
+        Ok(used)
+    }
+
+    async fn merge_tags(&self, from: &[String], into: &str) -> StoreResult<Vec<ToDo>> {
+        let mut conn = self.conn().await?;
+
+        let ids: Vec<usize> = redis::cmd("SUNION")
+            .arg(from.iter().map(|tag| tag_key(tag)).collect::<Vec<_>>())
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        let mut changed = vec![];
+        for todo in Self::fetch(&mut conn, &ids).await? {
+            let retag = |todo: &ToDo| retag_patch(todo, from, into);
+            if let Some(todo) = self.save_retrying(&mut conn, todo, retag).await? {
+                changed.push(todo);
+            }
+        }
+
+        Ok(changed)
+    }
+
+    async fn lists(&self) -> StoreResult<Vec<TaskList>> {
+        let mut conn = self.conn().await?;
+
+        let values: Vec<String> = redis::cmd("HVALS")
+            .arg(LISTS_KEY)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        Ok(values
+            .iter()
+            .filter_map(|value| {
+                serde_json::from_str(value)
+                    .map_err(|e| warn!("Skipping malformed list: {}", e))
+                    .ok()
+            })
+            .collect())
+    }
+
+    async fn get_list(&self, id: usize) -> StoreResult<Option<TaskList>> {
+        let mut conn = self.conn().await?;
+
+        let value: Option<String> = redis::cmd("HGET")
+            .arg(LISTS_KEY)
+            .arg(id)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+
+        value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(|e| {
+                error!("{}: {}", common::UNABLE_TO_PARSE_DATA, e);
+                ApiError::internal(common::UNABLE_TO_PARSE_DATA)
+            })
+    }
+
+    async fn create_list(&self, new_list: NewTaskList) -> StoreResult<TaskList> {
+        let mut conn = self.conn().await?;
+
+        let id: usize = redis::cmd("INCR")
+            .arg(NEXT_LIST_ID_KEY)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        let list = new_list.into_list(id);
+        let json = serde_json::to_string(&list)
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let stored: bool = redis::cmd("HSETNX")
+            .arg(LISTS_KEY)
+            .arg(id)
+            .arg(&json)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+        if !stored {
+            error!("{}: list id {} already in use", FAILED_TO_STORE_DATA, id);
+            return Err(ApiError::conflict(FAILED_TO_STORE_DATA));
+        }
+
+        Ok(list)
+    }
+
+    async fn update_list(&self, id: usize, patch: TaskListPatch) -> StoreResult<Option<TaskList>> {
+        let Some(mut list) = self.get_list(id).await? else {
+            return Ok(None);
+        };
+        list.apply(patch);
+        let json = serde_json::to_string(&list)
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        let mut conn = self.conn().await?;
+        let _: () = redis::cmd("HSET")
+            .arg(LISTS_KEY)
+            .arg(id)
+            .arg(&json)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+
+        Ok(Some(list))
+    }
+
+    async fn delete_list(&self, id: usize) -> StoreResult<Option<Vec<ToDo>>> {
+        let mut conn = self.conn().await?;
+
+        let exists: bool = redis::cmd("HEXISTS")
+            .arg(LISTS_KEY)
+            .arg(id)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+        if !exists {
+            return Ok(None);
+        }
+
+        // The todos are moved before the list is deleted, so that a failure
+        // leaves the list in place for the request to be sent again
+        let mut moved = vec![];
+        let mut tried = BTreeSet::new();
+        for _ in 0..LIST_ATTEMPTS {
+            let _: () = redis::cmd("WATCH")
+                .arg(list_key(id))
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            let ids: Vec<usize> = redis::cmd("SMEMBERS")
+                .arg(list_key(id))
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            let ids: Vec<usize> = ids.into_iter().filter(|id| tried.insert(*id)).collect();
+
+            if !ids.is_empty() {
+                Self::unwatch(&mut conn).await?;
+                for todo in Self::fetch(&mut conn, &ids).await? {
+                    let to_inbox =
+                        |todo: &ToDo| (todo.list_id == id).then(|| move_patch(INBOX_LIST_ID));
+                    if let Some(todo) = self.save_retrying(&mut conn, todo, to_inbox).await? {
+                        moved.push(todo);
+                    }
+                }
+                continue;
+            }
+
+            // Ids left in the set were missing or malformed
+            let deleted: Option<(bool,)> = redis::pipe()
+                .atomic()
+                .cmd("HDEL")
+                .arg(LISTS_KEY)
+                .arg(id)
+                .cmd("DEL")
+                .arg(list_key(id))
+                .ignore()
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_DELETE_DATA))?;
+            // Nil when the transaction was aborted by the WATCH
+            if let Some((deleted,)) = deleted {
+                return Ok(deleted.then_some(moved));
+            }
+        }
+
+        warn!("{}: list {}", LIST_CONFLICT, id);
+        Err(ApiError::conflict(LIST_CONFLICT))
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for RedisStore {
+    async fn claim_key(
+        &self,
+        key: &str,
+        pending: &Recorded,
+        ttl: Duration,
+    ) -> StoreResult<Option<Recorded>> {
+        let mut conn = self.conn().await?;
+        let json = serde_json::to_string(pending)
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        // The key may expire between SET and GET, it is then claimed again
+        for _ in 0..2 {
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(idempotency_key(key))
+                .arg(&json)
+                .arg("NX")
+                .arg("PX")
+                .arg(ttl.as_millis() as u64)
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_STORE_DATA))?;
+            if claimed.is_some() {
+                return Ok(None);
+            }
+
+            let recorded: Option<String> = redis::cmd("GET")
+                .arg(idempotency_key(key))
+                .query_async(&mut conn)
+                .await
+                .map_err(redis_error(FAILED_TO_RETRIEVE_DATA))?;
+            if let Some(recorded) = recorded {
+                let recorded = serde_json::from_str(&recorded).map_err(|e| {
+                    error!("{}: {}", common::UNABLE_TO_PARSE_DATA, e);
+                    ApiError::internal(common::UNABLE_TO_PARSE_DATA)
+                })?;
+                return Ok(Some(recorded));
+            }
+        }
+
+        error!(
+            "{}: Idempotency-Key {:?} keeps expiring",
+            FAILED_TO_STORE_DATA, key
+        );
+        Err(ApiError::internal(FAILED_TO_STORE_DATA))
+    }
+
+    async fn remember_key(&self, key: &str, done: &Recorded, window: Duration) -> StoreResult<()> {
+        let mut conn = self.conn().await?;
+        let json = serde_json::to_string(done)
+            .map_err(|_| ApiError::internal(common::UNABLE_TO_PARSE_DATA))?;
+
+        redis::cmd("SET")
+            .arg(idempotency_key(key))
+            .arg(&json)
+            .arg("PX")
+            .arg(window.as_millis() as u64)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_STORE_DATA))
+    }
+
+    async fn release_key(&self, key: &str) -> StoreResult<()> {
+        let mut conn = self.conn().await?;
+
+        redis::cmd("DEL")
+            .arg(idempotency_key(key))
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error(FAILED_TO_DELETE_DATA))
+    }
+}